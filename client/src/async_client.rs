@@ -0,0 +1,472 @@
+use std::{
+    io::{Read, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use libftp::{
+    command::{Command, RepresentationTypeKind},
+    reply::Reply,
+    transfer::{RepresentationDecoder, RepresentationEncoder, TextOptions},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    async_control::AsyncControl,
+    client::completion,
+    data::{
+        active_command, no_mode_left, passive_address, passive_command, AsyncDataConnection,
+        DataChannel,
+    },
+    DataMode, DataOptions, Error, Result,
+};
+
+const BUFFER_SIZE: usize = 1024 * 16;
+
+/// An async FTP client, with the same methods as the blocking `Client`.
+///
+/// Dropping any of its futures, or a `Download` before finishing it, is
+/// safe: the replies left unread are discarded before the next command.
+pub struct AsyncClient {
+    control: AsyncControl,
+    banner: Reply,
+    // The representation type last sent to the server.
+    representation: Option<RepresentationTypeKind>,
+    text_options: TextOptions,
+    data_channel: DataChannel,
+}
+
+impl AsyncClient {
+    /// Connects to a server and waits for it to be ready for a new user.
+    pub async fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        Self::from_stream(TcpStream::connect(address).await?).await
+    }
+
+    /// Uses an already connected control connection.
+    pub async fn from_stream(stream: TcpStream) -> Result<Self> {
+        let mut control = AsyncControl::new(stream);
+
+        // 120 Service ready in nnn minutes precedes the 220 reply.
+        let mut banner = control.receive().await?;
+        while banner.is_preliminary() {
+            banner = control.receive().await?;
+        }
+
+        if banner.code != *b"220" {
+            return Err(Error::Reply(banner));
+        }
+
+        Ok(Self {
+            control,
+            banner,
+            representation: None,
+            text_options: TextOptions::default(),
+            data_channel: DataChannel::new(DataOptions::default()),
+        })
+    }
+
+    /// The 220 reply the server greeted the client with.
+    pub fn banner(&self) -> &Reply {
+        &self.banner
+    }
+
+    pub fn control_stream(&self) -> &TcpStream {
+        self.control.stream()
+    }
+
+    pub async fn login(&mut self, user: &[u8], password: &[u8]) -> Result<()> {
+        self.login_with_account(user, password, None).await
+    }
+
+    /// Logs in, sending the account if the server asks for one.
+    pub async fn login_with_account(
+        &mut self,
+        user: &[u8],
+        password: &[u8],
+        account: Option<&[u8]>,
+    ) -> Result<()> {
+        let mut reply = self.execute(&Command::UserName(user.to_vec())).await?;
+        if reply.code == *b"331" {
+            reply = self.execute(&Command::Password(password.to_vec())).await?;
+        }
+
+        if let (b"332", Some(account)) = (&reply.code, account) {
+            reply = self.execute(&Command::Account(account.to_vec())).await?;
+        }
+
+        completion(reply).map(drop)
+    }
+
+    pub async fn pwd(&mut self) -> Result<Vec<u8>> {
+        let reply = self.command(&Command::PrintWorkingDirectory).await?;
+        reply.pathname().ok_or(Error::InvalidReply(reply))
+    }
+
+    pub async fn cwd(&mut self, path: &[u8]) -> Result<()> {
+        self.command(&Command::ChangeWorkingDirectory(path.to_vec()))
+            .await
+            .map(drop)
+    }
+
+    /// Creates a directory, returning its pathname as the server reports
+    /// it, or `path` if the server doesn't.
+    pub async fn mkdir(&mut self, path: &[u8]) -> Result<Vec<u8>> {
+        let reply = self.command(&Command::MakeDirectory(path.to_vec())).await?;
+        Ok(reply.pathname().unwrap_or_else(|| path.to_vec()))
+    }
+
+    pub async fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<()> {
+        let reply = self.execute(&Command::RenameFrom(from.to_vec())).await?;
+        if !reply.is_intermediate() {
+            return Err(Error::Reply(reply));
+        }
+
+        self.command(&Command::RenameTo(to.to_vec()))
+            .await
+            .map(drop)
+    }
+
+    pub async fn delete(&mut self, path: &[u8]) -> Result<()> {
+        self.command(&Command::Delete(path.to_vec()))
+            .await
+            .map(drop)
+    }
+
+    /// Sets the representation type of transfers, which is image unless set.
+    pub async fn set_type(&mut self, kind: RepresentationTypeKind) -> Result<()> {
+        self.command(&Command::RepresentationType(kind)).await?;
+        self.representation = Some(kind);
+        Ok(())
+    }
+
+    /// Sets how text transfers store text locally.
+    pub fn set_text_options(&mut self, text_options: TextOptions) {
+        self.text_options = text_options;
+    }
+
+    /// Sets how data connections are opened.
+    pub fn set_data_options(&mut self, data_options: DataOptions) {
+        self.data_channel = DataChannel::new(data_options);
+    }
+
+    /// Lists a directory, returning the listing as sent by the server.
+    /// `libftp::listing::parse_listing` parses it into entries.
+    pub async fn list(&mut self, path: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut listing = Vec::new();
+        let mut data = self
+            .start_transfer(&Command::List(path.map(<[u8]>::to_vec)))
+            .await?;
+        data.read_to_end(&mut listing).await?;
+        drop(data);
+        self.end_transfer().await?;
+        Ok(listing)
+    }
+
+    /// Lists the names in a directory.
+    pub async fn nlst(&mut self, path: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let mut listing = Vec::new();
+        let mut data = self
+            .start_transfer(&Command::NameList(path.map(<[u8]>::to_vec)))
+            .await?;
+        data.read_to_end(&mut listing).await?;
+        drop(data);
+        self.end_transfer().await?;
+
+        Ok(listing
+            .split(|&c| c == b'\n')
+            .map(|name| name.strip_suffix(b"\r").unwrap_or(name))
+            .filter(|name| !name.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect())
+    }
+
+    /// Starts downloading a file, which is read from the returned `Download`.
+    pub async fn retr(&mut self, path: &[u8]) -> Result<Download<'_>> {
+        let kind = self.transfer_type().await?;
+        let data = self
+            .start_transfer(&Command::Retrieve(path.to_vec()))
+            .await?;
+        let decoder = RepresentationDecoder::new(kind, Received::default(), &self.text_options);
+        Ok(Download {
+            client: self,
+            data,
+            decoder,
+        })
+    }
+
+    /// Uploads a file from `reader`, returning the amount of bytes read.
+    /// Dropping the future midway stores the part of the file sent so far.
+    pub async fn stor<R: AsyncRead + Unpin>(&mut self, path: &[u8], reader: &mut R) -> Result<u64> {
+        let kind = self.transfer_type().await?;
+        let mut data = self.start_transfer(&Command::Store(path.to_vec())).await?;
+        // Encodes into a buffer, which is then sent over the data connection.
+        let mut encoder = RepresentationEncoder::new(kind, Vec::new(), &self.text_options);
+        let mut input = vec![0u8; BUFFER_SIZE];
+        let mut amount = 0;
+        loop {
+            let read = reader.read(&mut input).await?;
+            if read == 0 {
+                break;
+            }
+
+            amount += read as u64;
+            encoder.write_all(&input[..read])?;
+            data.write_all(encoder.get_mut()).await?;
+            encoder.get_mut().clear();
+        }
+
+        data.write_all(&encoder.finish()?).await?;
+        // Closing the data connection ends the file.
+        data.shutdown().await?;
+        drop(data);
+        self.end_transfer().await?;
+        Ok(amount)
+    }
+
+    /// Logs out and closes the control connection.
+    pub async fn quit(mut self) -> Result<()> {
+        self.command(&Command::Logout).await.map(drop)
+    }
+
+    /// Sends a command, returning its reply whatever it is.
+    pub async fn execute(&mut self, command: &Command) -> Result<Reply> {
+        self.control.send(command).await?;
+        Ok(self.control.receive().await?)
+    }
+
+    /// Sends a command, expecting a positive completion reply.
+    pub async fn command(&mut self, command: &Command) -> Result<Reply> {
+        completion(self.execute(command).await?)
+    }
+
+    async fn transfer_type(&mut self) -> Result<RepresentationTypeKind> {
+        match self.representation {
+            Some(kind) => Ok(kind),
+            None => {
+                self.set_type(RepresentationTypeKind::Image).await?;
+                Ok(RepresentationTypeKind::Image)
+            }
+        }
+    }
+
+    // Opens a data connection in the first mode the server accepts.
+    async fn open_data(&mut self) -> Result<AsyncDataConnection> {
+        let mut refusal = None;
+        for mode in self.data_channel.modes() {
+            match self.request_data(mode).await {
+                Ok(Some(data)) => return Ok(data),
+                Ok(None) => {}
+                Err(error) if self.data_channel.refuse(mode, &error) => refusal = Some(error),
+                Err(error) => return Err(error),
+            }
+        }
+
+        Err(no_mode_left(refusal))
+    }
+
+    // Requests a data connection mode, returning `None` if it can't be used.
+    async fn request_data(&mut self, mode: DataMode) -> Result<Option<AsyncDataConnection>> {
+        if mode.is_passive() {
+            let reply = self.command(&passive_command(mode)).await?;
+            let peer = self.control.stream().peer_addr()?.ip();
+            let address = passive_address(mode, reply, peer, self.data_channel.options())?;
+            let timeout = self.data_channel.options().timeout;
+            return Ok(Some(AsyncDataConnection::connect(address, timeout).await?));
+        }
+
+        let listener = TcpListener::bind((self.control.stream().local_addr()?.ip(), 0)).await?;
+        let Some(command) = active_command(mode, listener.local_addr()?) else {
+            return Ok(None);
+        };
+
+        self.command(&command).await?;
+        Ok(Some(AsyncDataConnection::Listening(listener)))
+    }
+
+    // Opens a data connection and sends a transfer command over it,
+    // expecting the preliminary reply of a transfer starting.
+    async fn start_transfer(&mut self, command: &Command) -> Result<TcpStream> {
+        let data = self.open_data().await?;
+        let reply = self.execute(command).await?;
+        if !reply.is_preliminary() {
+            return Err(Error::Reply(reply));
+        }
+
+        Ok(data.establish(self.data_channel.options().timeout).await?)
+    }
+
+    async fn end_transfer(&mut self) -> Result<()> {
+        completion(self.control.receive().await?).map(drop)
+    }
+}
+
+/// A file being downloaded, converted from its representation type as
+/// it's read. Once read to the end, `finish` receives the server's
+/// confirmation of the transfer.
+pub struct Download<'a> {
+    client: &'a mut AsyncClient,
+    data: TcpStream,
+    decoder: RepresentationDecoder<Received>,
+}
+
+impl Download<'_> {
+    /// Closes the data connection and waits for the reply ending the
+    /// transfer, which is an error if the file wasn't read to the end.
+    pub async fn finish(self) -> Result<()> {
+        drop(self.data);
+        self.client.end_transfer().await
+    }
+}
+
+impl AsyncRead for Download<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match this.decoder.read(buf.initialize_unfilled()) {
+                Ok(read) => {
+                    buf.advance(read);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(error) => return Poll::Ready(Err(error)),
+            }
+
+            // The decoder consumed everything received so far.
+            let mut input = [0u8; BUFFER_SIZE];
+            let mut input = ReadBuf::new(&mut input);
+            ready!(Pin::new(&mut this.data).poll_read(cx, &mut input))?;
+            let received = this.decoder.get_mut();
+            received.data.extend_from_slice(input.filled());
+            received.ended = input.filled().is_empty();
+        }
+    }
+}
+
+// Data received from the data connection, which the blocking decoders read
+// from. Reading it once empty blocks until more data is received.
+#[derive(Default)]
+struct Received {
+    data: Vec<u8>,
+    ended: bool,
+}
+
+impl Read for Received {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.data.is_empty() && !self.ended {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+
+        let amount = buf.len().min(self.data.len());
+        buf[..amount].copy_from_slice(&self.data[..amount]);
+        self.data.drain(..amount);
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libftp::{
+        command::RepresentationTypeKind,
+        transfer::{LineEnding, TextOptions},
+    };
+    use tokio::io::AsyncReadExt;
+
+    use crate::{test_server::TestServer, AsyncClient, DataMode, DataOptions};
+
+    async fn client(server: &TestServer) -> AsyncClient {
+        let mut client = AsyncClient::connect(server.address()).await.unwrap();
+        client.login(b"user", b"pass").await.unwrap();
+        client
+    }
+
+    async fn download(client: &mut AsyncClient, path: &[u8]) -> Vec<u8> {
+        let mut download = client.retr(path).await.unwrap();
+        let mut output = Vec::new();
+        download.read_to_end(&mut output).await.unwrap();
+        download.finish().await.unwrap();
+        output
+    }
+
+    #[tokio::test]
+    async fn test_transfers() {
+        let server = TestServer::start();
+        let mut client = client(&server).await;
+
+        let data = b"binary \r\n data".repeat(1000);
+        assert_eq!(client.stor(b"a.bin", &mut &data[..]).await.unwrap(), 14_000);
+        assert_eq!(server.file(b"/a.bin"), Some(data.clone()));
+
+        client.rename(b"a.bin", b"b.bin").await.unwrap();
+        assert_eq!(client.nlst(None).await.unwrap(), [b"b.bin"]);
+        assert_eq!(download(&mut client, b"b.bin").await, data);
+        client.quit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_active_mode() {
+        let server = TestServer::refusing(&["EPRT"]);
+        let mut client = client(&server).await;
+        client.set_data_options(DataOptions {
+            modes: vec![DataMode::ExtendedActive, DataMode::Active],
+            ..Default::default()
+        });
+
+        client.stor(b"a", &mut &b"data"[..]).await.unwrap();
+        assert_eq!(download(&mut client, b"a").await, b"data");
+    }
+
+    #[tokio::test]
+    async fn test_text_transfers() {
+        let server = TestServer::start();
+        let mut client = client(&server).await;
+        client.set_text_options(TextOptions {
+            line_ending: LineEnding::Lf,
+            ..Default::default()
+        });
+        client
+            .set_type(RepresentationTypeKind::Ascii(None))
+            .await
+            .unwrap();
+
+        let text = b"first line\nsecond line\r\n".repeat(2000);
+        client.stor(b"a.txt", &mut &text[..]).await.unwrap();
+        let sent = server.file(b"/a.txt").unwrap();
+        assert_eq!(sent, b"first line\r\nsecond line\r\n".repeat(2000));
+        assert_eq!(
+            download(&mut client, b"a.txt").await,
+            b"first line\nsecond line\n".repeat(2000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_futures() {
+        let server = TestServer::start();
+        let mut client = client(&server).await;
+        let data = vec![7u8; 4 << 20];
+        client.stor(b"large", &mut &data[..]).await.unwrap();
+
+        // Dropped after reading part of the file, leaving the reply ending
+        // the transfer unread.
+        let mut download = client.retr(b"large").await.unwrap();
+        let mut start = [0u8; 10];
+        download.read_exact(&mut start).await.unwrap();
+        drop(download);
+        assert_eq!(client.pwd().await.unwrap(), b"/");
+
+        // Dropped after sending the command, but before its reply.
+        tokio::select! {
+            biased;
+            _ = client.mkdir(b"docs") => panic!("the reply arrived immediately"),
+            _ = std::future::ready(()) => {}
+        }
+        client.cwd(b"docs").await.unwrap();
+        assert_eq!(client.pwd().await.unwrap(), b"/docs");
+    }
+}
@@ -0,0 +1,105 @@
+use libftp::{
+    command::Command,
+    parser::parse_reply,
+    reply::Reply,
+    serializer::{CommandSerializer, Serializer},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const BUFFER_SIZE: usize = 1024 * 16;
+
+/// The control connection of the async client. Whatever is sent or received
+/// is kept in the connection itself rather than in a future, so dropping a
+/// future halfway leaves the connection in a state the next command recovers.
+pub(crate) struct AsyncControl {
+    stream: TcpStream,
+    // Received bytes not parsed yet.
+    incoming: Vec<u8>,
+    // Serialized commands not written yet.
+    outgoing: Vec<u8>,
+    // Final replies the server still owes for what was sent, starting
+    // with the greeting.
+    owed: usize,
+}
+
+impl AsyncControl {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            owed: 1,
+        }
+    }
+
+    pub fn stream(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Sends a command, first discarding the replies to commands
+    /// whose futures were dropped before receiving them.
+    pub async fn send(&mut self, command: &Command) -> std::io::Result<()> {
+        self.settle().await?;
+        CommandSerializer::new(&mut self.outgoing).serialize(command)?;
+        self.owed += 1;
+        self.flush().await
+    }
+
+    pub async fn receive(&mut self) -> std::io::Result<Reply> {
+        loop {
+            match parse_reply(&self.incoming) {
+                Ok((unparsed, reply)) => {
+                    let parsed = self.incoming.len() - unparsed.len();
+                    self.incoming.drain(..parsed);
+                    if !reply.is_preliminary() {
+                        self.owed = self.owed.saturating_sub(1);
+                    }
+
+                    return Ok(reply);
+                }
+                Err(nom::Err::Incomplete(_)) => {
+                    // Reading is cancel safe: nothing was read if the future is dropped.
+                    let mut input = [0u8; BUFFER_SIZE];
+                    let read = self.stream.read(&mut input).await?;
+                    if read == 0 {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into());
+                    }
+
+                    self.incoming.extend_from_slice(&input[..read]);
+                }
+                Err(_) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "malformed reply",
+                    ))
+                }
+            }
+        }
+    }
+
+    // Finishes sending a command and receives every reply still owed.
+    async fn settle(&mut self) -> std::io::Result<()> {
+        self.flush().await?;
+        while self.owed > 0 {
+            self.receive().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        while !self.outgoing.is_empty() {
+            let written = self.stream.write(&self.outgoing).await?;
+            if written == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+
+            self.outgoing.drain(..written);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,916 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use libftp::{
+    command::{Command, ProtectionLevel, RepresentationTypeKind},
+    listing::{parse_mlsd_listing, DateTime, ListEntry},
+    reply::Reply,
+    transfer::{RepresentationDecoder, RepresentationEncoder, TextOptions},
+};
+
+use crate::{
+    control::Control,
+    data::{
+        active_command, no_mode_left, passive_address, passive_command, DataChannel, DataConnection,
+    },
+    tls::Stream,
+    DataMode, DataOptions, Error, Result, TlsConfig,
+};
+
+/// A blocking FTP client. Every reply other than the one expected,
+/// usually a positive completion, is returned as `Error::Reply`.
+pub struct Client {
+    control: Control,
+    banner: Reply,
+    // The representation type last sent to the server.
+    representation: Option<RepresentationTypeKind>,
+    text_options: TextOptions,
+    data_channel: DataChannel,
+    tls: Option<TlsConfig>,
+    // Whether data connections are secured by TLS, after `PROT P`.
+    protect_data: bool,
+    // Whether replies may be out of step with commands.
+    broken: bool,
+}
+
+impl Client {
+    /// Connects to a server and waits for it to be ready for a new user.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        Self::from_stream(TcpStream::connect(address)?)
+    }
+
+    /// Uses an already connected control connection.
+    pub fn from_stream(stream: TcpStream) -> Result<Self> {
+        Self::greeted(Control::new(Stream::new(stream)))
+    }
+
+    /// Connects to a server with implicit FTPS, securing the connection
+    /// before being greeted, then protects data connections. Servers
+    /// usually listen for it on `IMPLICIT_TLS_PORT`.
+    pub fn connect_implicit_tls(address: impl ToSocketAddrs, tls: TlsConfig) -> Result<Self> {
+        let mut stream = Stream::new(TcpStream::connect(address)?);
+        stream.secure(tls.connection()?)?;
+
+        let mut client = Self::greeted(Control::new(stream))?;
+        client.tls = Some(tls);
+        client.set_data_protection(ProtectionLevel::Private)?;
+        Ok(client)
+    }
+
+    fn greeted(mut control: Control) -> Result<Self> {
+        // 120 Service ready in nnn minutes precedes the 220 reply.
+        let mut banner = control.receive()?;
+        while banner.is_preliminary() {
+            banner = control.receive()?;
+        }
+
+        if banner.code != *b"220" {
+            return Err(Error::Reply(banner));
+        }
+
+        Ok(Self {
+            control,
+            banner,
+            representation: None,
+            text_options: TextOptions::default(),
+            data_channel: DataChannel::new(DataOptions::default()),
+            tls: None,
+            protect_data: false,
+            broken: false,
+        })
+    }
+
+    /// The 220 reply the server greeted the client with.
+    pub fn banner(&self) -> &Reply {
+        &self.banner
+    }
+
+    pub fn control_stream(&self) -> &TcpStream {
+        self.control.stream()
+    }
+
+    /// Whether the control connection failed, or a failed transfer couldn't
+    /// be ended, so replies may be out of step with commands. Every command
+    /// fails from then on.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// Secures the control connection with `AUTH TLS`, then protects
+    /// data connections. Usually done before logging in, to keep the
+    /// password from being sent in the clear.
+    pub fn auth_tls(&mut self, tls: TlsConfig) -> Result<()> {
+        let reply = self.execute(&Command::Authentication(b"TLS".to_vec()))?;
+        if reply.code != *b"234" {
+            return Err(Error::Reply(reply));
+        }
+
+        let secured = self.control.secure(&tls);
+        self.in_step(secured)?;
+        self.tls = Some(tls);
+        self.set_data_protection(ProtectionLevel::Private)
+    }
+
+    /// Sets whether data connections are secured by TLS, which is only
+    /// possible once TLS is set up. `PBSZ 0` is sent first as RFC 4217 requires.
+    pub fn set_data_protection(&mut self, level: ProtectionLevel) -> Result<()> {
+        let private = level == ProtectionLevel::Private;
+        if private && self.tls.is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "data connections can only be protected once TLS is set up",
+            )
+            .into());
+        }
+
+        self.command(&Command::ProtectionBufferSize(0))?;
+        self.command(&Command::DataChannelProtection(level))?;
+        self.protect_data = private;
+        Ok(())
+    }
+
+    /// Ends TLS on the control connection with `CCC`, as NAT devices need
+    /// to read the commands opening data connections. Data connections
+    /// stay protected.
+    pub fn clear_command_channel(&mut self) -> Result<()> {
+        self.command(&Command::ClearCommandChannel)?;
+        let unsecured = self.control.unsecure();
+        self.in_step(unsecured)
+    }
+
+    /// Resets the session with `REIN`, which logs out while keeping the
+    /// connection open to log in again. Servers may also end TLS on it.
+    pub fn reinitialize(&mut self) -> Result<()> {
+        // 120 Service ready in nnn minutes may precede the 220 reply.
+        let mut reply = self.execute(&Command::Reinitialize)?;
+        while reply.is_preliminary() {
+            reply = self.receive()?;
+        }
+
+        completion(reply)?;
+        self.representation = None;
+        Ok(())
+    }
+
+    pub fn login(&mut self, user: &[u8], password: &[u8]) -> Result<()> {
+        self.login_with_account(user, password, None)
+    }
+
+    /// Logs in, sending the account if the server asks for one.
+    pub fn login_with_account(
+        &mut self,
+        user: &[u8],
+        password: &[u8],
+        account: Option<&[u8]>,
+    ) -> Result<()> {
+        let mut reply = self.execute(&Command::UserName(user.to_vec()))?;
+        if reply.code == *b"331" {
+            reply = self.execute(&Command::Password(password.to_vec()))?;
+        }
+
+        if let (b"332", Some(account)) = (&reply.code, account) {
+            reply = self.execute(&Command::Account(account.to_vec()))?;
+        }
+
+        completion(reply).map(drop)
+    }
+
+    pub fn pwd(&mut self) -> Result<Vec<u8>> {
+        let reply = self.command(&Command::PrintWorkingDirectory)?;
+        reply.pathname().ok_or(Error::InvalidReply(reply))
+    }
+
+    pub fn cwd(&mut self, path: &[u8]) -> Result<()> {
+        self.command(&Command::ChangeWorkingDirectory(path.to_vec()))
+            .map(drop)
+    }
+
+    /// Creates a directory, returning its pathname as the server reports
+    /// it, or `path` if the server doesn't.
+    pub fn mkdir(&mut self, path: &[u8]) -> Result<Vec<u8>> {
+        let reply = self.command(&Command::MakeDirectory(path.to_vec()))?;
+        Ok(reply.pathname().unwrap_or_else(|| path.to_vec()))
+    }
+
+    pub fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<()> {
+        let reply = self.execute(&Command::RenameFrom(from.to_vec()))?;
+        if !reply.is_intermediate() {
+            return Err(Error::Reply(reply));
+        }
+
+        self.command(&Command::RenameTo(to.to_vec())).map(drop)
+    }
+
+    pub fn rmdir(&mut self, path: &[u8]) -> Result<()> {
+        self.command(&Command::RemoveDirectory(path.to_vec()))
+            .map(drop)
+    }
+
+    pub fn delete(&mut self, path: &[u8]) -> Result<()> {
+        self.command(&Command::Delete(path.to_vec())).map(drop)
+    }
+
+    /// Sets the representation type of transfers, which is image unless set.
+    pub fn set_type(&mut self, kind: RepresentationTypeKind) -> Result<()> {
+        self.command(&Command::RepresentationType(kind))?;
+        self.representation = Some(kind);
+        Ok(())
+    }
+
+    /// Sets how text transfers store text locally.
+    pub fn set_text_options(&mut self, text_options: TextOptions) {
+        self.text_options = text_options;
+    }
+
+    /// Sets how data connections are opened.
+    pub fn set_data_options(&mut self, data_options: DataOptions) {
+        self.data_channel = DataChannel::new(data_options);
+    }
+
+    /// Lists a directory, returning the listing as sent by the server.
+    /// `libftp::listing::parse_listing` parses it into entries.
+    pub fn list(&mut self, path: Option<&[u8]>) -> Result<Vec<u8>> {
+        self.transfer(&Command::List(path.map(<[u8]>::to_vec)), 0, read_all)
+    }
+
+    /// Lists the names in a directory.
+    pub fn nlst(&mut self, path: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let listing = self.transfer(&Command::NameList(path.map(<[u8]>::to_vec)), 0, read_all)?;
+        Ok(listing
+            .split(|&c| c == b'\n')
+            .map(|name| name.strip_suffix(b"\r").unwrap_or(name))
+            .filter(|name| !name.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect())
+    }
+
+    /// Lists a directory with `MLSD`, which servers supporting it advertise
+    /// as the `MLST` feature.
+    pub fn mlsd(&mut self, path: Option<&[u8]>) -> Result<Vec<ListEntry>> {
+        let command = Command::MachineListDirectory(path.map(<[u8]>::to_vec));
+        let listing = self.transfer(&command, 0, read_all)?;
+        Ok(parse_mlsd_listing(&listing))
+    }
+
+    /// The features the server advertises, such as `MDTM` or `MLST type*;size*;`,
+    /// which are none for servers not implementing `FEAT`.
+    pub fn features(&mut self) -> Result<Vec<Vec<u8>>> {
+        match self.command(&Command::Features) {
+            Ok(reply) => Ok(reply.features()),
+            Err(Error::Reply(reply)) if matches!(&reply.code, b"500" | b"502") => Ok(Vec::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Downloads a file into `writer`, returning the amount of bytes written.
+    pub fn retr<W: Write>(&mut self, path: &[u8], writer: &mut W) -> Result<u64> {
+        let kind = self.transfer_type()?;
+        let text_options = self.text_options.clone();
+        self.transfer(&Command::Retrieve(path.to_vec()), 0, |data| {
+            let mut decoder = RepresentationDecoder::new(kind, data, &text_options);
+            std::io::copy(&mut decoder, writer)
+        })
+    }
+
+    /// Uploads a file from `reader`, returning the amount of bytes read.
+    pub fn stor<R: Read>(&mut self, path: &[u8], reader: &mut R) -> Result<u64> {
+        let kind = self.transfer_type()?;
+        let text_options = self.text_options.clone();
+        let timeout = self.data_channel.options().timeout;
+        self.transfer(&Command::Store(path.to_vec()), 0, |data| {
+            let mut encoder = RepresentationEncoder::new(kind, data, &text_options);
+            let amount = std::io::copy(reader, &mut encoder)?;
+            // Closing the data connection ends the file.
+            encoder.finish()?.close(timeout)?;
+            Ok(amount)
+        })
+    }
+
+    /// The size of a file in octets, which depends on the representation type.
+    pub fn size(&mut self, path: &[u8]) -> Result<u64> {
+        let reply = self.command(&Command::FileSize(path.to_vec()))?;
+        reply.size().ok_or(Error::InvalidReply(reply))
+    }
+
+    /// The time a file was last modified, in UTC.
+    pub fn modified(&mut self, path: &[u8]) -> Result<DateTime> {
+        let reply = self.command(&Command::ModificationTime(path.to_vec()))?;
+        reply.modification_time().ok_or(Error::InvalidReply(reply))
+    }
+
+    /// Downloads the rest of a file, continuing after what `file` already
+    /// holds, and returns the amount of bytes written. When the server
+    /// implements `MDTM`, a partial file older than the remote file is
+    /// downloaded again, and the time of the remote file is set on `file`
+    /// once complete. Only image transfers can be resumed.
+    pub fn resume_retr(&mut self, path: &[u8], file: &mut File) -> Result<u64> {
+        self.require_image_type()?;
+        let size = self.size(path)?;
+        let modified = self.modified_if_implemented(path)?;
+
+        let metadata = file.metadata()?;
+        let mut offset = metadata.len();
+        let changed = modified.is_some_and(|modified| {
+            offset > 0
+                && metadata
+                    .modified()
+                    .is_ok_and(|local| modified > local.into())
+        });
+        if offset > size || changed {
+            file.set_len(0)?;
+            offset = 0;
+        }
+
+        let mut amount = 0;
+        if offset < size {
+            file.seek(SeekFrom::Start(offset))?;
+            let command = Command::Retrieve(path.to_vec());
+            amount = self.transfer(&command, offset, |mut data| std::io::copy(&mut data, file))?;
+        }
+
+        check_size(offset + amount, size)?;
+        if let Some(modified) = modified {
+            file.set_modified(modified.to_system_time())?;
+        }
+
+        Ok(amount)
+    }
+
+    /// Uploads the rest of `file`, continuing after what the server already
+    /// holds, and returns the amount of bytes read. Only image transfers
+    /// can be resumed.
+    pub fn resume_stor(&mut self, path: &[u8], file: &mut File) -> Result<u64> {
+        self.require_image_type()?;
+        let length = file.metadata()?.len();
+        let offset = match self.size(path) {
+            Ok(size) => size,
+            // The file doesn't exist yet.
+            Err(Error::Reply(reply)) if reply.code == *b"550" => 0,
+            Err(error) => return Err(error),
+        };
+        if offset > length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the remote file is larger than the local file",
+            )
+            .into());
+        }
+
+        let mut amount = 0;
+        if offset < length {
+            file.seek(SeekFrom::Start(offset))?;
+            let timeout = self.data_channel.options().timeout;
+            amount = self.transfer(&Command::Store(path.to_vec()), offset, |mut data| {
+                let amount = std::io::copy(file, &mut data)?;
+                data.close(timeout)?;
+                Ok(amount)
+            })?;
+        }
+
+        check_size(self.size(path)?, length)?;
+        Ok(amount)
+    }
+
+    /// Logs out and closes the control connection.
+    pub fn quit(mut self) -> Result<()> {
+        self.command(&Command::Logout).map(drop)
+    }
+
+    /// Sends a command, returning its reply whatever it is.
+    pub fn execute(&mut self, command: &Command) -> Result<Reply> {
+        self.require_in_step()?;
+        let sent = self.control.send(command);
+        self.in_step(sent)?;
+        self.receive()
+    }
+
+    /// Sends a command, expecting a positive completion reply.
+    pub fn command(&mut self, command: &Command) -> Result<Reply> {
+        completion(self.execute(command)?)
+    }
+
+    fn receive(&mut self) -> Result<Reply> {
+        self.require_in_step()?;
+        let received = self.control.receive();
+        self.in_step(received)
+    }
+
+    fn require_in_step(&self) -> Result<()> {
+        if self.broken {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "the replies of the control connection are out of step",
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // Marks the client broken when the control connection fails, as what
+    // the server received and replied since is unknown. Commands refused
+    // before being sent leave it in step.
+    fn in_step<T>(&mut self, result: std::io::Result<T>) -> Result<T> {
+        if let Err(error) = &result {
+            self.broken |= error.kind() != std::io::ErrorKind::InvalidInput;
+        }
+
+        Ok(result?)
+    }
+
+    // The representation type set last, if any.
+    pub(crate) fn representation(&self) -> Option<RepresentationTypeKind> {
+        self.representation
+    }
+
+    fn transfer_type(&mut self) -> Result<RepresentationTypeKind> {
+        match self.representation {
+            Some(kind) => Ok(kind),
+            None => {
+                self.set_type(RepresentationTypeKind::Image)?;
+                Ok(RepresentationTypeKind::Image)
+            }
+        }
+    }
+
+    // Offsets of resumed transfers only count octets in image transfers.
+    pub(crate) fn require_image_type(&mut self) -> Result<()> {
+        if self.transfer_type()? != RepresentationTypeKind::Image {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "only image transfers can be resumed",
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // The time a file was last modified, or `None` if the server doesn't implement `MDTM`.
+    fn modified_if_implemented(&mut self, path: &[u8]) -> Result<Option<DateTime>> {
+        match self.modified(path) {
+            Ok(modified) => Ok(Some(modified)),
+            Err(Error::Reply(reply)) if matches!(&reply.code, b"500" | b"502") => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    // Opens a data connection in the first mode the server accepts.
+    fn open_data(&mut self) -> Result<DataConnection> {
+        let mut refusal = None;
+        for mode in self.data_channel.modes() {
+            match self.request_data(mode) {
+                Ok(Some(data)) => return Ok(data),
+                Ok(None) => {}
+                Err(error) if self.data_channel.refuse(mode, &error) => refusal = Some(error),
+                Err(error) => return Err(error),
+            }
+        }
+
+        Err(no_mode_left(refusal))
+    }
+
+    // Requests a data connection mode, returning `None` if it can't be used.
+    fn request_data(&mut self, mode: DataMode) -> Result<Option<DataConnection>> {
+        if mode.is_passive() {
+            let reply = self.command(&passive_command(mode))?;
+            let peer = self.control.stream().peer_addr()?.ip();
+            let address = passive_address(mode, reply, peer, self.data_channel.options())?;
+            let timeout = self.data_channel.options().timeout;
+            let stream = TcpStream::connect_timeout(&address, timeout)?;
+            return Ok(Some(DataConnection::Connected(stream)));
+        }
+
+        let listener = TcpListener::bind((self.control.stream().local_addr()?.ip(), 0))?;
+        let Some(command) = active_command(mode, listener.local_addr()?) else {
+            return Ok(None);
+        };
+
+        self.command(&command)?;
+        Ok(Some(DataConnection::Listening(listener)))
+    }
+
+    // Runs a transfer, giving its data connection to `run`, which closes
+    // it by dropping it. If `run` fails, the transfer is aborted, so the
+    // next command doesn't read the reply ending the transfer as its own.
+    fn transfer<T>(
+        &mut self,
+        command: &Command,
+        offset: u64,
+        run: impl FnOnce(Stream) -> std::io::Result<T>,
+    ) -> Result<T> {
+        let data = self.start_transfer_from(command, offset)?;
+        match run(data) {
+            Ok(value) => {
+                self.end_transfer()?;
+                Ok(value)
+            }
+            Err(error) => {
+                self.abort_failed_transfer();
+                Err(error.into())
+            }
+        }
+    }
+
+    // Starts a transfer at an offset into the file, which `REST` sets
+    // right before the transfer command as RFC 959 requires.
+    pub(crate) fn start_transfer_from(&mut self, command: &Command, offset: u64) -> Result<Stream> {
+        let data = self.open_data()?;
+        if offset > 0 {
+            let reply = self.execute(&Command::Restart(offset.to_string().into_bytes()))?;
+            if !reply.is_intermediate() {
+                return Err(Error::Reply(reply));
+            }
+        }
+
+        let reply = self.execute(command)?;
+        if !reply.is_preliminary() {
+            return Err(Error::Reply(reply));
+        }
+
+        let timeout = self.data_channel.options().timeout;
+        let tls = self.tls.as_ref().filter(|_| self.protect_data);
+        let established = data.establish(timeout).and_then(|socket| {
+            let mut stream = Stream::new(socket);
+            if let Some(tls) = tls {
+                stream.secure(tls.connection()?)?;
+            }
+
+            Ok(stream)
+        });
+        established.map_err(|error| {
+            self.abort_failed_transfer();
+            error.into()
+        })
+    }
+
+    pub(crate) fn end_transfer(&mut self) -> Result<()> {
+        completion(self.receive()?).map(drop)
+    }
+
+    // Ends a transfer before the end of the file by closing the data
+    // connection and sending `ABOR`. The transfer is answered first, with
+    // 426 if it was aborted or 226 if the server had sent everything.
+    pub(crate) fn abort_transfer(&mut self, data: Stream) -> Result<()> {
+        drop(data);
+        self.execute(&Command::Abort)?;
+        completion(self.receive()?).map(drop)
+    }
+
+    // Aborts a transfer which failed once started, after its data connection
+    // was closed. The client is broken if that fails, as it's unknown which
+    // replies are left.
+    pub(crate) fn abort_failed_transfer(&mut self) {
+        if self
+            .execute(&Command::Abort)
+            .and_then(|_| self.receive())
+            .is_err()
+        {
+            self.broken = true;
+        }
+    }
+}
+
+fn read_all(mut data: Stream) -> std::io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    data.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+// Checks the size of a file after a resumed transfer.
+fn check_size(size: u64, expected: u64) -> Result<()> {
+    if size != expected {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("the file has {size} bytes after the transfer instead of {expected}"),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+pub(crate) fn completion(reply: Reply) -> Result<Reply> {
+    if reply.is_completion() {
+        Ok(reply)
+    } else {
+        Err(Error::Reply(reply))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::Write,
+        net::Ipv4Addr,
+        path::PathBuf,
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use libftp::{
+        command::{ProtectionLevel, RepresentationTypeKind},
+        listing::DateTime,
+    };
+
+    use crate::{test_server::TestServer, Client, DataMode, DataOptions, Error, PassiveAddress};
+
+    fn client(server: &TestServer) -> Client {
+        let mut client = Client::connect(server.address()).unwrap();
+        client.login(b"user", b"pass").unwrap();
+        client
+    }
+
+    fn temp_file(name: &str) -> (PathBuf, File) {
+        let path = std::env::temp_dir().join(format!("libftp-{}-{name}", std::process::id()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn test_directories() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+
+        assert_eq!(client.mkdir(b"docs").unwrap(), b"/docs");
+        client.cwd(b"docs").unwrap();
+        assert_eq!(client.pwd().unwrap(), b"/docs");
+        client.quit().unwrap();
+    }
+
+    #[test]
+    fn test_transfers() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+
+        let data = b"binary \r\n data".repeat(1000);
+        assert_eq!(client.stor(b"a.bin", &mut &data[..]).unwrap(), 14_000);
+        assert_eq!(server.file(b"/a.bin"), Some(data.clone()));
+
+        client.rename(b"a.bin", b"b.bin").unwrap();
+        assert_eq!(client.nlst(None).unwrap(), [b"b.bin"]);
+        let listing = client.list(None).unwrap();
+        assert!(listing.ends_with(b" b.bin\r\n"));
+
+        let mut output = Vec::new();
+        client.retr(b"b.bin", &mut output).unwrap();
+        assert_eq!(output, data);
+
+        client.delete(b"b.bin").unwrap();
+        assert_eq!(server.file(b"/b.bin"), None);
+    }
+
+    #[test]
+    fn test_data_modes() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+
+        for mode in [
+            DataMode::ExtendedPassive,
+            DataMode::Passive,
+            DataMode::ExtendedActive,
+            DataMode::Active,
+        ] {
+            client.set_data_options(DataOptions {
+                modes: vec![mode],
+                ..Default::default()
+            });
+            let data = format!("{mode:?}").into_bytes();
+            client.stor(b"mode", &mut &data[..]).unwrap();
+
+            let mut output = Vec::new();
+            client.retr(b"mode", &mut output).unwrap();
+            assert_eq!(output, data);
+        }
+    }
+
+    #[test]
+    fn test_data_mode_fallback() {
+        let server = TestServer::refusing(&["EPSV", "PASV"]);
+        let mut client = client(&server);
+
+        client.stor(b"a", &mut &b"data"[..]).unwrap();
+        assert_eq!(server.file(b"/a"), Some(b"data".to_vec()));
+
+        client.set_data_options(DataOptions {
+            modes: vec![DataMode::Passive],
+            ..Default::default()
+        });
+        let error = client.nlst(None).unwrap_err();
+        assert_eq!(error.reply().map(|reply| reply.code), Some(*b"500"));
+    }
+
+    #[test]
+    fn test_skip_passive_address() {
+        let server = TestServer::advertising(Ipv4Addr::new(192, 0, 2, 1));
+        let mut client = client(&server);
+        client.set_data_options(DataOptions {
+            modes: vec![DataMode::Passive],
+            passive_address: PassiveAddress::ControlPeer,
+            ..Default::default()
+        });
+
+        assert!(client.nlst(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_auth_tls() {
+        let (server, tls) = TestServer::with_tls(false);
+        let mut client = Client::connect(server.address()).unwrap();
+        client.auth_tls(tls).unwrap();
+        client.login(b"user", b"pass").unwrap();
+
+        // Every data connection resumes the TLS session, or the server aborts it.
+        let data = b"secret".repeat(1000);
+        for _ in 0..3 {
+            client.stor(b"a", &mut &data[..]).unwrap();
+            let mut output = Vec::new();
+            client.retr(b"a", &mut output).unwrap();
+            assert_eq!(output, data);
+        }
+
+        client.clear_command_channel().unwrap();
+        assert_eq!(client.pwd().unwrap(), b"/");
+        assert_eq!(client.nlst(None).unwrap(), [b"a"]);
+        client.quit().unwrap();
+    }
+
+    #[test]
+    fn test_close_times_out() {
+        let (server, tls) = TestServer::with_lingering_tls();
+        let mut client = Client::connect(server.address()).unwrap();
+        client.auth_tls(tls).unwrap();
+        client.login(b"user", b"pass").unwrap();
+        client.set_data_options(DataOptions {
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+        client.stor(b"a", &mut &b"data"[..]).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.file(b"/a"), Some(b"data".to_vec()));
+    }
+
+    #[test]
+    fn test_implicit_tls() {
+        let (server, tls) = TestServer::with_tls(true);
+        let mut client = Client::connect_implicit_tls(server.address(), tls).unwrap();
+        client.login(b"user", b"pass").unwrap();
+
+        client.stor(b"a", &mut &b"data"[..]).unwrap();
+        assert_eq!(server.file(b"/a"), Some(b"data".to_vec()));
+        client.set_data_protection(ProtectionLevel::Clear).unwrap();
+        assert_eq!(client.nlst(None).unwrap(), [b"a"]);
+    }
+
+    #[test]
+    fn test_resume_retr() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let modified = DateTime::new(2020, 1, 1, 0, 0, 0);
+        server.put_file(b"/a.bin", &data, modified);
+
+        // A download cut off after 4000 bytes.
+        let (path, mut file) = temp_file("resume_retr");
+        file.write_all(&data[..4000]).unwrap();
+
+        assert_eq!(client.resume_retr(b"a.bin", &mut file).unwrap(), 6000);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        let local = DateTime::from(file.metadata().unwrap().modified().unwrap());
+        assert_eq!(local, modified);
+        assert_eq!(client.resume_retr(b"a.bin", &mut file).unwrap(), 0);
+
+        // The remote file changed since the partial download.
+        let changed = data.iter().map(|c| !c).collect::<Vec<_>>();
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        server.put_file(b"/a.bin", &changed, later.into());
+        file.set_len(4000).unwrap();
+
+        assert_eq!(client.resume_retr(b"a.bin", &mut file).unwrap(), 10_000);
+        assert_eq!(std::fs::read(&path).unwrap(), changed);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_resume_stor() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let (path, mut file) = temp_file("resume_stor");
+        file.write_all(&data).unwrap();
+
+        // An upload cut off after 4000 bytes.
+        client.stor(b"a.bin", &mut &data[..4000]).unwrap();
+
+        assert_eq!(client.resume_stor(b"a.bin", &mut file).unwrap(), 6000);
+        assert_eq!(server.file(b"/a.bin"), Some(data));
+        assert_eq!(client.resume_stor(b"a.bin", &mut file).unwrap(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_errors_carry_the_reply() {
+        let server = TestServer::start();
+        let mut client = Client::connect(server.address()).unwrap();
+
+        match client.login(b"user", b"wrong") {
+            Err(Error::Reply(reply)) => assert_eq!(reply.code, *b"530"),
+            result => panic!("unexpected {result:?}"),
+        }
+
+        client.login(b"user", b"pass").unwrap();
+        let error = client.retr(b"missing", &mut Vec::new()).unwrap_err();
+        assert_eq!(error.reply().map(|reply| reply.code), Some(*b"550"));
+
+        // The client is still usable after a refused command.
+        assert_eq!(client.pwd().unwrap(), b"/");
+    }
+
+    // Accepts a few bytes, then fails.
+    struct FailingIo(usize);
+
+    impl Write for FailingIo {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 < buf.len() {
+                return Err(std::io::Error::other("disk full"));
+            }
+
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl std::io::Read for FailingIo {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0 == 0 {
+                return Err(std::io::Error::other("unreadable"));
+            }
+
+            let amount = buf.len().min(self.0);
+            buf[..amount].fill(b'x');
+            self.0 -= amount;
+            Ok(amount)
+        }
+    }
+
+    #[test]
+    fn test_failed_transfer_keeps_replies_in_step() {
+        let server = TestServer::start();
+        server.put_file(
+            b"/big.bin",
+            &[7; 1 << 20],
+            DateTime::new(2020, 1, 1, 0, 0, 0),
+        );
+        let mut client = client(&server);
+        client.set_type(RepresentationTypeKind::Image).unwrap();
+
+        let error = client.retr(b"big.bin", &mut FailingIo(1000)).unwrap_err();
+        assert!(matches!(error, Error::Io(_)));
+        assert!(!client.is_broken());
+        assert_eq!(client.pwd().unwrap(), b"/");
+
+        let error = client.stor(b"a.bin", &mut FailingIo(1000)).unwrap_err();
+        assert!(matches!(error, Error::Io(_)));
+        assert_eq!(client.pwd().unwrap(), b"/");
+    }
+
+    #[test]
+    fn test_line_breaks_are_refused() {
+        let server = TestServer::start();
+        let mut client = client(&server);
+        client.mkdir(b"docs").unwrap();
+
+        match client.cwd(b"docs\r\nRMD docs") {
+            Err(Error::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput),
+            result => panic!("unexpected {result:?}"),
+        }
+
+        client.cwd(b"docs").unwrap();
+    }
+
+    #[test]
+    fn test_account() {
+        let server = TestServer::start();
+        let mut client = Client::connect(server.address()).unwrap();
+
+        assert!(client.login(b"account", b"pass").is_err());
+        client
+            .login_with_account(b"account", b"pass", Some(b"billing"))
+            .unwrap();
+    }
+}
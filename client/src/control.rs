@@ -0,0 +1,71 @@
+use std::{io::Read, net::TcpStream};
+
+use libftp::{
+    command::Command,
+    parser::parse_reply,
+    reply::Reply,
+    serializer::{CommandSerializer, Serializer},
+};
+
+use crate::tls::{Stream, TlsConfig};
+
+const BUFFER_SIZE: usize = 1024 * 16;
+
+/// The control connection, sending commands and parsing replies.
+pub(crate) struct Control {
+    stream: Stream,
+    // Received bytes not parsed yet.
+    buffer: Vec<u8>,
+}
+
+impl Control {
+    pub fn new(stream: Stream) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn stream(&self) -> &TcpStream {
+        self.stream.socket()
+    }
+
+    pub fn secure(&mut self, tls: &TlsConfig) -> std::io::Result<()> {
+        self.stream.secure(tls.connection()?)
+    }
+
+    pub fn unsecure(&mut self) -> std::io::Result<()> {
+        self.stream.unsecure()
+    }
+
+    pub fn send(&mut self, command: &Command) -> std::io::Result<()> {
+        CommandSerializer::new(&mut self.stream).serialize(command)
+    }
+
+    pub fn receive(&mut self) -> std::io::Result<Reply> {
+        loop {
+            match parse_reply(&self.buffer) {
+                Ok((unparsed, reply)) => {
+                    let parsed = self.buffer.len() - unparsed.len();
+                    self.buffer.drain(..parsed);
+                    return Ok(reply);
+                }
+                Err(nom::Err::Incomplete(_)) => {
+                    let mut input = [0u8; BUFFER_SIZE];
+                    let read = self.stream.read(&mut input)?;
+                    if read == 0 {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into());
+                    }
+
+                    self.buffer.extend_from_slice(&input[..read]);
+                }
+                Err(_) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "malformed reply",
+                    ))
+                }
+            }
+        }
+    }
+}
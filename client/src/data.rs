@@ -0,0 +1,305 @@
+//! Data connections, opened passively by connecting to the server, or
+//! actively by listening for the server to connect to the client.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use libftp::{command::Command, reply::Reply};
+
+use crate::{Error, Result};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+// How often a listener is checked for the server connecting.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A way of opening data connections.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DataMode {
+    /// `EPSV`, connecting to a port of the server's control connection address.
+    ExtendedPassive,
+    /// `PASV`, connecting to an IPv4 address the server sends.
+    Passive,
+    /// `EPRT`, listening on an IPv4 or IPv6 address.
+    ExtendedActive,
+    /// `PORT`, listening on an IPv4 address.
+    Active,
+}
+
+impl DataMode {
+    pub(crate) fn is_passive(self) -> bool {
+        matches!(self, DataMode::ExtendedPassive | DataMode::Passive)
+    }
+}
+
+/// The address to connect to in passive mode.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PassiveAddress {
+    /// The address in the 227 reply.
+    Reply,
+    /// The peer address of the control connection, for servers behind a NAT
+    /// sending their private address. Only the port of the reply is used.
+    ControlPeer,
+    /// A given address, with the port of the reply.
+    Fixed(IpAddr),
+}
+
+/// How data connections are opened.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DataOptions {
+    /// The modes to try, in order of preference. A mode the server
+    /// doesn't implement is skipped for the rest of the session.
+    pub modes: Vec<DataMode>,
+    /// How long to wait for connecting to the server, or for the
+    /// server to connect to the client.
+    pub timeout: Duration,
+    /// The address to connect to in `PASV` mode.
+    pub passive_address: PassiveAddress,
+    /// Overrides `passive_address` for servers, by the peer address
+    /// of their control connection.
+    pub passive_address_overrides: HashMap<IpAddr, PassiveAddress>,
+}
+
+impl Default for DataOptions {
+    fn default() -> Self {
+        Self {
+            modes: vec![
+                DataMode::ExtendedPassive,
+                DataMode::Passive,
+                DataMode::ExtendedActive,
+                DataMode::Active,
+            ],
+            timeout: DEFAULT_TIMEOUT,
+            passive_address: PassiveAddress::Reply,
+            passive_address_overrides: HashMap::new(),
+        }
+    }
+}
+
+impl DataOptions {
+    /// The address to connect to in `PASV` mode for the server at `peer`.
+    pub fn passive_address_for(&self, peer: IpAddr) -> PassiveAddress {
+        self.passive_address_overrides
+            .get(&peer)
+            .copied()
+            .unwrap_or(self.passive_address)
+    }
+}
+
+/// The data connection options of a session, and the modes the server refused.
+pub(crate) struct DataChannel {
+    options: DataOptions,
+    refused: Vec<DataMode>,
+}
+
+impl DataChannel {
+    pub fn new(options: DataOptions) -> Self {
+        Self {
+            options,
+            refused: Vec::new(),
+        }
+    }
+
+    pub fn options(&self) -> &DataOptions {
+        &self.options
+    }
+
+    /// The modes to try, in order.
+    pub fn modes(&self) -> Vec<DataMode> {
+        self.options
+            .modes
+            .iter()
+            .copied()
+            .filter(|mode| !self.refused.contains(mode))
+            .collect()
+    }
+
+    /// Remembers a mode as refused if `error` is a 500 or 502 reply to the
+    /// command requesting it, returning whether it was.
+    pub fn refuse(&mut self, mode: DataMode, error: &Error) -> bool {
+        let refused =
+            matches!(error, Error::Reply(reply) if matches!(&reply.code, b"500" | b"502"));
+        if refused {
+            self.refused.push(mode);
+        }
+
+        refused
+    }
+}
+
+/// The error when every mode was refused or can't be used.
+pub(crate) fn no_mode_left(refusal: Option<Error>) -> Error {
+    refusal.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "no data connection mode is available",
+        )
+        .into()
+    })
+}
+
+/// The command requesting a passive mode.
+pub(crate) fn passive_command(mode: DataMode) -> Command {
+    match mode {
+        DataMode::ExtendedPassive => Command::ExtendedPassive(None),
+        _ => Command::Passive,
+    }
+}
+
+/// The address the reply to a passive mode command tells to connect to.
+/// `EPSV` only sends a port, which is of the control connection's `peer`.
+pub(crate) fn passive_address(
+    mode: DataMode,
+    reply: Reply,
+    peer: IpAddr,
+    options: &DataOptions,
+) -> Result<SocketAddr> {
+    if mode == DataMode::ExtendedPassive {
+        let port = reply.extended_passive_port();
+        return port
+            .map(|port| SocketAddr::new(peer, port))
+            .ok_or(Error::InvalidReply(reply));
+    }
+
+    let Some(address) = reply.passive_address() else {
+        return Err(Error::InvalidReply(reply));
+    };
+
+    let ip = IpAddr::V4(*address.ip());
+    if ip != peer && (is_internal(ip) != is_internal(peer)) {
+        log::warn!("server {peer} sent the address {ip} to connect to in passive mode");
+    }
+
+    let ip = match options.passive_address_for(peer) {
+        PassiveAddress::Reply => ip,
+        PassiveAddress::ControlPeer => peer,
+        PassiveAddress::Fixed(ip) => ip,
+    };
+
+    Ok(SocketAddr::new(ip, address.port()))
+}
+
+// Whether an address is private or loopback, and so unreachable over the internet.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback() || ip.to_ipv4_mapped().is_some_and(|ip| is_internal(ip.into()))
+        }
+    }
+}
+
+/// The command telling the server the address an active mode listens on,
+/// or `None` if the mode can't carry the address.
+pub(crate) fn active_command(mode: DataMode, address: SocketAddr) -> Option<Command> {
+    match (mode, address) {
+        (DataMode::ExtendedActive, address) => Some(Command::ExtendedDataPort(address)),
+        (DataMode::Active, SocketAddr::V4(address)) => {
+            Some(Command::DataPort(*address.ip(), address.port()))
+        }
+        _ => None,
+    }
+}
+
+/// A data connection, either connected or listening for the server.
+pub(crate) enum DataConnection {
+    Connected(TcpStream),
+    Listening(TcpListener),
+}
+
+impl DataConnection {
+    /// Waits for the server to connect if listening for it.
+    pub fn establish(self, timeout: Duration) -> std::io::Result<TcpStream> {
+        let listener = match self {
+            Self::Connected(stream) => return Ok(stream),
+            Self::Listening(listener) => listener,
+        };
+
+        listener.set_nonblocking(true)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    return Ok(stream);
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            "the server didn't open the data connection",
+                        ));
+                    }
+
+                    sleep(ACCEPT_INTERVAL);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+/// The async counterpart of `DataConnection`.
+#[cfg(feature = "tokio")]
+pub(crate) enum AsyncDataConnection {
+    Connected(tokio::net::TcpStream),
+    Listening(tokio::net::TcpListener),
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncDataConnection {
+    pub async fn connect(address: SocketAddr, timeout: Duration) -> std::io::Result<Self> {
+        let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        Ok(Self::Connected(stream))
+    }
+
+    pub async fn establish(self, timeout: Duration) -> std::io::Result<tokio::net::TcpStream> {
+        match self {
+            Self::Connected(stream) => Ok(stream),
+            Self::Listening(listener) => tokio::time::timeout(timeout, listener.accept())
+                .await
+                .map_err(|_| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "the server didn't open the data connection",
+                    )
+                })?
+                .map(|(stream, _)| stream),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+
+    use libftp::reply::Reply;
+
+    use super::{passive_address, DataMode, DataOptions, PassiveAddress};
+
+    #[test]
+    fn test_passive_address() {
+        let private = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 2121);
+        let peer = IpAddr::from([203, 0, 113, 5]);
+        let other = IpAddr::from([203, 0, 113, 6]);
+        let address = |options: &DataOptions, peer| {
+            let reply = Reply::entering_passive_mode(private);
+            passive_address(DataMode::Passive, reply, peer, options).unwrap()
+        };
+
+        let mut options = DataOptions::default();
+        assert_eq!(address(&options, peer), SocketAddr::V4(private));
+
+        options.passive_address = PassiveAddress::ControlPeer;
+        options
+            .passive_address_overrides
+            .insert(other, PassiveAddress::Reply);
+        assert_eq!(address(&options, peer), SocketAddr::new(peer, 2121));
+        assert_eq!(address(&options, other), SocketAddr::V4(private));
+    }
+}
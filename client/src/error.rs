@@ -0,0 +1,63 @@
+use libftp::reply::Reply;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The server refused a command, or replied to it unexpectedly.
+    Reply(Reply),
+    /// A reply whose text couldn't be understood, such as a
+    /// 227 reply without an address.
+    InvalidReply(Reply),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The reply the error was caused by, if any.
+    pub fn reply(&self) -> Option<&Reply> {
+        match self {
+            Error::Io(_) => None,
+            Error::Reply(reply) | Error::InvalidReply(reply) => Some(reply),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => error.fmt(f),
+            Error::Reply(reply) => write!(f, "server replied {}", ReplyDisplay(reply)),
+            Error::InvalidReply(reply) => write!(f, "invalid reply {}", ReplyDisplay(reply)),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+// Shows the code and the last line of a reply.
+struct ReplyDisplay<'a>(&'a Reply);
+
+impl std::fmt::Display for ReplyDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = self.0.lines().last().unwrap_or_default();
+        write!(
+            f,
+            "{} {}",
+            String::from_utf8_lossy(&self.0.code),
+            String::from_utf8_lossy(line)
+        )
+    }
+}
@@ -0,0 +1,28 @@
+#[cfg(feature = "tokio")]
+mod async_client;
+#[cfg(feature = "tokio")]
+mod async_control;
+mod client;
+mod control;
+mod data;
+mod error;
+mod mirror;
+mod netrc;
+mod pool;
+mod segmented;
+#[cfg(test)]
+mod test_server;
+mod tls;
+mod url;
+
+#[cfg(feature = "tokio")]
+pub use async_client::{AsyncClient, Download};
+pub use client::Client;
+pub use data::{DataMode, DataOptions, PassiveAddress};
+pub use error::{Error, Result};
+pub use mirror::{MirrorOptions, MirrorReport};
+pub use netrc::{Netrc, NetrcEntry, NetrcError};
+pub use pool::{Pool, PoolOptions, PooledClient, SessionReset};
+pub use segmented::segmented_retr;
+pub use tls::{TlsConfig, IMPLICIT_TLS_PORT};
+pub use url::{FtpUrl, TypeCode, UrlError};
@@ -0,0 +1,591 @@
+//! Mirroring directory trees between the local file system and a server,
+//! transferring only the files which are missing or changed.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fs::File,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use libftp::{
+    command::Command,
+    listing::{parse_listing, DateTime, EntryKind, ListEntry},
+    reply::Reply,
+};
+
+use crate::{Client, Error, Result};
+
+// Listings such as `ls -l` only show the minute a file was modified.
+const TIME_TOLERANCE: i64 = 60;
+
+/// How a directory tree is mirrored.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct MirrorOptions {
+    /// Deletes files and directories missing from the source tree.
+    pub delete: bool,
+}
+
+/// What mirroring a directory tree did, by paths relative to the tree.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct MirrorReport {
+    /// Files which were missing or changed.
+    pub transferred: Vec<Vec<u8>>,
+    /// The amount of bytes transferred.
+    pub bytes: u64,
+    /// Files with the same size as their source, which isn't newer.
+    pub unchanged: Vec<Vec<u8>>,
+    pub created_directories: Vec<Vec<u8>>,
+    /// Files and directories missing from the source tree.
+    pub deleted: Vec<Vec<u8>>,
+    /// Entries which are neither files nor directories, such as symbolic links.
+    pub skipped: Vec<Vec<u8>>,
+}
+
+impl std::fmt::Display for MirrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} transferred ({} bytes), {} unchanged, {} directories created, {} deleted, {} skipped",
+            self.transferred.len(),
+            self.bytes,
+            self.unchanged.len(),
+            self.created_directories.len(),
+            self.deleted.len(),
+            self.skipped.len()
+        )
+    }
+}
+
+// How remote directories are listed.
+enum Lister {
+    Mlsd,
+    /// `LIST`, parsed with the reply to `SYST` as a hint for the format.
+    List(Option<Reply>),
+}
+
+impl Client {
+    /// Makes the local directory `local` a copy of the remote directory
+    /// `remote`. Downloaded files get the modification time of the remote file.
+    pub fn mirror_down(
+        &mut self,
+        remote: &[u8],
+        local: &Path,
+        options: &MirrorOptions,
+    ) -> Result<MirrorReport> {
+        let lister = self.lister()?;
+        let mut report = MirrorReport::default();
+        std::fs::create_dir_all(local)?;
+        self.mirror_down_directory(&lister, remote, local, b"", options, &mut report)?;
+        Ok(report)
+    }
+
+    /// Makes the remote directory `remote` a copy of the local directory `local`.
+    pub fn mirror_up(
+        &mut self,
+        local: &Path,
+        remote: &[u8],
+        options: &MirrorOptions,
+    ) -> Result<MirrorReport> {
+        let lister = self.lister()?;
+        let mut report = MirrorReport::default();
+        match self.mkdir(remote) {
+            // The directory may exist already.
+            Err(Error::Reply(reply)) if reply.code == *b"550" => {}
+            result => {
+                result?;
+            }
+        }
+        self.mirror_up_directory(&lister, local, remote, b"", options, &mut report)?;
+        Ok(report)
+    }
+
+    fn lister(&mut self) -> Result<Lister> {
+        let machine_listing = self.features()?.iter().any(|feature| {
+            let name = feature.split(|&c| c == b' ').next().unwrap_or_default();
+            name.eq_ignore_ascii_case(b"MLST")
+        });
+        if machine_listing {
+            return Ok(Lister::Mlsd);
+        }
+
+        let system = self.execute(&Command::System)?;
+        Ok(Lister::List(system.is_completion().then_some(system)))
+    }
+
+    // The entries of a remote directory by name, without the directory and its
+    // parent, which `MLSD` tells by type and `LIST` by name.
+    fn entries(&mut self, lister: &Lister, directory: &[u8]) -> Result<Vec<ListEntry>> {
+        let mut entries = match lister {
+            Lister::Mlsd => self.mlsd(Some(directory))?,
+            Lister::List(system) => {
+                let listing = self.list(Some(directory))?;
+                let now = DateTime::from(SystemTime::now());
+                parse_listing(&listing, system.as_ref(), now)
+            }
+        };
+
+        entries.retain(|entry| {
+            !matches!(
+                entry.kind,
+                EntryKind::CurrentDirectory | EntryKind::ParentDirectory
+            ) && !matches!(&entry.name[..], b"." | b"..")
+        });
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn mirror_down_directory(
+        &mut self,
+        lister: &Lister,
+        remote: &[u8],
+        local: &Path,
+        relative: &[u8],
+        options: &MirrorOptions,
+        report: &mut MirrorReport,
+    ) -> Result<()> {
+        let mut names = BTreeSet::new();
+        for entry in self.entries(lister, remote)? {
+            if !is_plain_name(&entry.name) {
+                report.skipped.push(join(relative, &entry.name));
+                continue;
+            }
+
+            let remote_path = join(remote, &entry.name);
+            let local_path = local.join(local_name(&entry.name));
+            let relative_path = join(relative, &entry.name);
+            names.insert(local_name(&entry.name));
+
+            let local_kind = std::fs::symlink_metadata(&local_path)
+                .ok()
+                .map(|metadata| metadata.is_dir());
+            match entry.kind {
+                EntryKind::Directory => {
+                    if local_kind == Some(false)
+                        && !replace_local(&local_path, &relative_path, options, report)?
+                    {
+                        continue;
+                    }
+
+                    if !local_path.is_dir() {
+                        std::fs::create_dir(&local_path)?;
+                        report.created_directories.push(relative_path.clone());
+                    }
+
+                    self.mirror_down_directory(
+                        lister,
+                        &remote_path,
+                        &local_path,
+                        &relative_path,
+                        options,
+                        report,
+                    )?;
+                }
+                EntryKind::File => {
+                    let copy = std::fs::metadata(&local_path)
+                        .ok()
+                        .map(|metadata| ListEntry::from_metadata(Vec::new(), &metadata));
+                    if copy.is_some_and(|copy| {
+                        copy.kind == EntryKind::File && !is_outdated(&copy, &entry)
+                    }) {
+                        report.unchanged.push(relative_path);
+                        continue;
+                    }
+
+                    if local_kind == Some(true)
+                        && !replace_local(&local_path, &relative_path, options, report)?
+                    {
+                        continue;
+                    }
+
+                    let mut file = File::create(&local_path)?;
+                    report.bytes += self.retr(&remote_path, &mut file)?;
+                    if let Some(modified) = entry.modified {
+                        file.set_modified(modified.to_system_time())?;
+                    }
+
+                    report.transferred.push(relative_path);
+                }
+                _ => report.skipped.push(relative_path),
+            }
+        }
+
+        if options.delete {
+            let mut local_entries =
+                std::fs::read_dir(local)?.collect::<std::io::Result<Vec<_>>>()?;
+            local_entries.sort_by_key(|entry| entry.file_name());
+            for entry in local_entries {
+                if names.contains(Path::new(&entry.file_name())) {
+                    continue;
+                }
+
+                if entry.file_type()?.is_dir() {
+                    std::fs::remove_dir_all(entry.path())?;
+                } else {
+                    std::fs::remove_file(entry.path())?;
+                }
+
+                let name = remote_name(entry.file_name());
+                report.deleted.push(join(relative, &name));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mirror_up_directory(
+        &mut self,
+        lister: &Lister,
+        local: &Path,
+        remote: &[u8],
+        relative: &[u8],
+        options: &MirrorOptions,
+        report: &mut MirrorReport,
+    ) -> Result<()> {
+        let mut remote_entries: BTreeMap<_, _> = self
+            .entries(lister, remote)?
+            .into_iter()
+            .map(|entry| (entry.name.clone(), entry))
+            .collect();
+
+        let mut local_entries = std::fs::read_dir(local)?.collect::<std::io::Result<Vec<_>>>()?;
+        local_entries.sort_by_key(|entry| entry.file_name());
+        for local_entry in local_entries {
+            let name = remote_name(local_entry.file_name());
+            let entry = ListEntry::from_metadata(name.clone(), &local_entry.metadata()?);
+            let remote_entry = remote_entries.remove(&name);
+            let remote_path = join(remote, &name);
+            let relative_path = join(relative, &name);
+
+            match entry.kind {
+                EntryKind::Directory => {
+                    if remote_entry.is_none_or(|copy| copy.kind != EntryKind::Directory) {
+                        self.mkdir(&remote_path)?;
+                        report.created_directories.push(relative_path.clone());
+                    }
+
+                    self.mirror_up_directory(
+                        lister,
+                        &local_entry.path(),
+                        &remote_path,
+                        &relative_path,
+                        options,
+                        report,
+                    )?;
+                }
+                EntryKind::File => {
+                    if remote_entry.is_some_and(|copy| {
+                        copy.kind == EntryKind::File && !is_outdated(&copy, &entry)
+                    }) {
+                        report.unchanged.push(relative_path);
+                        continue;
+                    }
+
+                    let mut file = File::open(local_entry.path())?;
+                    report.bytes += self.stor(&remote_path, &mut file)?;
+                    report.transferred.push(relative_path);
+                }
+                _ => report.skipped.push(relative_path),
+            }
+        }
+
+        // What's left is missing from the local directory.
+        if options.delete {
+            for (name, entry) in remote_entries {
+                if !is_plain_name(&name) {
+                    report.skipped.push(join(relative, &name));
+                    continue;
+                }
+
+                let remote_path = join(remote, &name);
+                match entry.kind {
+                    EntryKind::Directory => self.remove_tree(lister, &remote_path)?,
+                    _ => self.delete(&remote_path)?,
+                }
+
+                report.deleted.push(join(relative, &name));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Deletes a remote directory with everything in it.
+    fn remove_tree(&mut self, lister: &Lister, directory: &[u8]) -> Result<()> {
+        for entry in self.entries(lister, directory)? {
+            // Not to delete outside the tree, which fails removing it instead.
+            if !is_plain_name(&entry.name) {
+                continue;
+            }
+
+            let path = join(directory, &entry.name);
+            match entry.kind {
+                EntryKind::Directory => self.remove_tree(lister, &path)?,
+                _ => self.delete(&path)?,
+            }
+        }
+
+        self.rmdir(directory)
+    }
+}
+
+// Removes a local entry which the remote one of another kind replaces, if
+// deleting is allowed, or else skips it. Returns whether it was removed.
+fn replace_local(
+    path: &Path,
+    relative: &[u8],
+    options: &MirrorOptions,
+    report: &mut MirrorReport,
+) -> Result<bool> {
+    if !options.delete {
+        report.skipped.push(relative.to_vec());
+        return Ok(false);
+    }
+
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+
+    report.deleted.push(relative.to_vec());
+    Ok(true)
+}
+
+// Whether a copy of a file is outdated, by its size differing from the
+// source, or by the source being newer. What a listing lacks is ignored.
+fn is_outdated(copy: &ListEntry, source: &ListEntry) -> bool {
+    let resized = matches!((copy.size, source.size), (Some(copy), Some(source)) if copy != source);
+    let older = match (copy.modified, source.modified) {
+        (Some(copy), Some(source)) => source.timestamp() > copy.timestamp() + TIME_TOLERANCE,
+        _ => false,
+    };
+
+    resized || older
+}
+
+// Whether a name from a listing names an entry of the local directory,
+// rather than a path leading out of it, as a malicious server might send.
+fn is_plain_name(name: &[u8]) -> bool {
+    let path = local_name(name);
+    let mut components = path.components();
+    !name.contains(&b'/')
+        && matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+}
+
+// Joins a name to a remote path, or to the empty path of the tree's root.
+fn join(directory: &[u8], name: &[u8]) -> Vec<u8> {
+    match directory {
+        b"" => name.to_vec(),
+        _ if directory.ends_with(b"/") => [directory, name].concat(),
+        _ => [directory, b"/", name].concat(),
+    }
+}
+
+#[cfg(unix)]
+fn local_name(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(std::ffi::OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+fn local_name(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).into_owned())
+}
+
+#[cfg(unix)]
+fn remote_name(name: OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+
+    name.into_vec()
+}
+
+#[cfg(not(unix))]
+fn remote_name(name: OsString) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use libftp::listing::{DateTime, EntryKind, ListEntry};
+
+    use super::MirrorOptions;
+    use crate::{test_server::TestServer, Client};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("libftp-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_mirror_down() {
+        let server = TestServer::start();
+        let mut client = Client::connect(server.address()).unwrap();
+        client.login(b"user", b"pass").unwrap();
+        client.mkdir(b"/site").unwrap();
+        client.mkdir(b"/site/docs").unwrap();
+        let modified = DateTime::new(2020, 1, 1, 0, 0, 0);
+        server.put_file(b"/site/a.txt", b"a", modified);
+        server.put_file(b"/site/docs/b.txt", b"b", modified);
+
+        let local = temp_dir("mirror_down");
+        let options = MirrorOptions::default();
+        let report = client.mirror_down(b"/site", &local, &options).unwrap();
+        assert_eq!(report.transferred, [&b"a.txt"[..], b"docs/b.txt"]);
+        assert_eq!(report.created_directories, [b"docs"]);
+        assert_eq!(std::fs::read(local.join("docs/b.txt")).unwrap(), b"b");
+
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        server.put_file(b"/site/docs/b.txt", b"B", later.into());
+        std::fs::write(local.join("extra.txt"), b"extra").unwrap();
+        let options = MirrorOptions { delete: true };
+        let report = client.mirror_down(b"/site", &local, &options).unwrap();
+        assert_eq!(report.transferred, [b"docs/b.txt"]);
+        assert_eq!(report.unchanged, [b"a.txt"]);
+        assert_eq!(report.deleted, [b"extra.txt"]);
+        assert_eq!(std::fs::read(local.join("docs/b.txt")).unwrap(), b"B");
+        assert!(!local.join("extra.txt").exists());
+
+        std::fs::remove_dir_all(local).unwrap();
+    }
+
+    #[test]
+    fn test_mirror_down_kind_changes() {
+        let server = TestServer::start();
+        let mut client = Client::connect(server.address()).unwrap();
+        client.login(b"user", b"pass").unwrap();
+        client.mkdir(b"/site").unwrap();
+        client.mkdir(b"/site/docs").unwrap();
+        let modified = DateTime::new(2020, 1, 1, 0, 0, 0);
+        server.put_file(b"/site/a.txt", b"a", modified);
+        server.put_file(b"/site/docs/b.txt", b"b", modified);
+
+        // Each remote entry is of another kind locally.
+        let local = temp_dir("mirror_down_kind_changes");
+        std::fs::create_dir_all(local.join("a.txt")).unwrap();
+        std::fs::write(local.join("docs"), b"docs").unwrap();
+
+        let report = client
+            .mirror_down(b"/site", &local, &MirrorOptions::default())
+            .unwrap();
+        assert_eq!(report.skipped, [&b"a.txt"[..], b"docs"]);
+        assert!(report.transferred.is_empty());
+        assert!(local.join("a.txt").is_dir());
+
+        let options = MirrorOptions { delete: true };
+        let report = client.mirror_down(b"/site", &local, &options).unwrap();
+        assert_eq!(report.deleted, [&b"a.txt"[..], b"docs"]);
+        assert_eq!(report.transferred, [&b"a.txt"[..], b"docs/b.txt"]);
+        assert_eq!(std::fs::read(local.join("a.txt")).unwrap(), b"a");
+        assert_eq!(std::fs::read(local.join("docs/b.txt")).unwrap(), b"b");
+
+        std::fs::remove_dir_all(local).unwrap();
+    }
+
+    #[test]
+    fn test_mirror_down_skips_paths() {
+        let server = TestServer::start();
+        let mut client = Client::connect(server.address()).unwrap();
+        client.login(b"user", b"pass").unwrap();
+        client.mkdir(b"/site").unwrap();
+        server.put_file(b"/site/a.txt", b"a", DateTime::new(2020, 1, 1, 0, 0, 0));
+        let names: [&[u8]; 4] = [b"../escape.txt", b"/tmp/escape.txt", b"a/../../x", b"sub/"];
+        for name in names {
+            server.forge_entry(
+                b"/site",
+                ListEntry {
+                    name: name.to_vec(),
+                    kind: EntryKind::File,
+                    ..Default::default()
+                },
+            );
+        }
+        server.forge_entry(
+            b"/site",
+            ListEntry {
+                name: b"../escaped".to_vec(),
+                kind: EntryKind::Directory,
+                ..Default::default()
+            },
+        );
+        // The directory and its parent aren't entries, whatever their names.
+        for (name, kind) in [
+            (&b"/site"[..], EntryKind::CurrentDirectory),
+            (b"/", EntryKind::ParentDirectory),
+        ] {
+            server.forge_entry(
+                b"/site",
+                ListEntry {
+                    name: name.to_vec(),
+                    kind,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let local = temp_dir("mirror_down_skips_paths");
+        let report = client
+            .mirror_down(b"/site", &local, &MirrorOptions::default())
+            .unwrap();
+        assert_eq!(report.transferred, [b"a.txt"]);
+        let mut skipped = report.skipped;
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                &b"../escape.txt"[..],
+                b"../escaped",
+                b"/tmp/escape.txt",
+                b"a/../../x",
+                b"sub/"
+            ]
+        );
+        assert!(!local.join("../escape.txt").exists());
+        assert!(!local.join("../escaped").exists());
+
+        std::fs::remove_dir_all(local).unwrap();
+    }
+
+    #[test]
+    fn test_mirror_up_with_list() {
+        // Without `FEAT`, the server isn't known to implement `MLSD`.
+        let server = TestServer::refusing(&["FEAT"]);
+        let mut client = Client::connect(server.address()).unwrap();
+        client.login(b"user", b"pass").unwrap();
+
+        let local = temp_dir("mirror_up");
+        std::fs::create_dir_all(local.join("docs")).unwrap();
+        std::fs::write(local.join("a.txt"), b"a").unwrap();
+        std::fs::write(local.join("docs/b.txt"), b"b").unwrap();
+
+        let options = MirrorOptions { delete: true };
+        let report = client.mirror_up(&local, b"/site", &options).unwrap();
+        assert_eq!(report.transferred, [&b"a.txt"[..], b"docs/b.txt"]);
+        assert_eq!(report.bytes, 2);
+        assert_eq!(server.file(b"/site/docs/b.txt"), Some(b"b".to_vec()));
+
+        std::fs::remove_file(local.join("a.txt")).unwrap();
+        let report = client.mirror_up(&local, b"/site", &options).unwrap();
+        assert!(report.transferred.is_empty());
+        assert_eq!(report.unchanged, [b"docs/b.txt"]);
+        assert_eq!(report.deleted, [b"a.txt"]);
+        assert_eq!(server.file(b"/site/a.txt"), None);
+        assert_eq!(
+            report.to_string(),
+            "0 transferred (0 bytes), 1 unchanged, 0 directories created, 1 deleted, 0 skipped"
+        );
+
+        std::fs::remove_dir_all(local).unwrap();
+    }
+}
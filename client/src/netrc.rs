@@ -0,0 +1,447 @@
+//! `.netrc` files, which keep credentials by host for FTP clients.
+
+use std::{collections::BTreeMap, fs::File, io::Read, path::Path};
+
+use crate::{
+    url::{ANONYMOUS_PASSWORD, ANONYMOUS_USER},
+    Client, Result,
+};
+
+/// The credentials and macros for a machine, or for any
+/// other machine if it's the `default` entry.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct NetrcEntry {
+    /// The machine name, or `None` for the default entry.
+    pub machine: Option<String>,
+    pub login: Option<Vec<u8>>,
+    pub password: Option<Vec<u8>>,
+    pub account: Option<Vec<u8>>,
+    /// The `macdef` bodies by name, each line ending with a newline.
+    pub macros: BTreeMap<String, Vec<u8>>,
+}
+
+/// The way a `.netrc` file couldn't be parsed.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct NetrcError {
+    /// The line the error is on, starting from 1.
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for NetrcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for NetrcError {}
+
+/// A parsed `.netrc` file.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Netrc {
+    pub entries: Vec<NetrcEntry>,
+}
+
+impl Netrc {
+    pub fn parse(text: &[u8]) -> std::result::Result<Self, NetrcError> {
+        let mut tokens = Tokens {
+            text,
+            position: 0,
+            line: 1,
+        };
+        let mut entries: Vec<NetrcEntry> = Vec::new();
+        while let Some(token) = tokens.next() {
+            let error = |message| NetrcError {
+                line: tokens.line,
+                message,
+            };
+            match &token[..] {
+                b"machine" => {
+                    let name = tokens.value()?;
+                    entries.push(NetrcEntry {
+                        machine: Some(String::from_utf8_lossy(&name).into_owned()),
+                        ..Default::default()
+                    });
+                }
+                b"default" => entries.push(NetrcEntry::default()),
+                b"login" | b"password" | b"account" | b"macdef" => {
+                    let entry = entries
+                        .last_mut()
+                        .ok_or(error("expected machine or default first"))?;
+                    let value = tokens.value()?;
+                    match &token[..] {
+                        b"login" => entry.login = Some(value),
+                        b"password" => entry.password = Some(value),
+                        b"account" => entry.account = Some(value),
+                        _ => {
+                            let name = String::from_utf8_lossy(&value).into_owned();
+                            entry.macros.insert(name, tokens.macro_body());
+                        }
+                    }
+                }
+                _ => return Err(error("unknown token")),
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Reads a `.netrc` file, which must be owned by the user and not be
+    /// accessible by others if it has passwords other than for anonymous
+    /// logins, as `ftp` requires.
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let netrc = Self::parse(&contents)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+            let secret = netrc.entries.iter().any(|entry| {
+                entry.password.is_some() && entry.login.as_deref() != Some(ANONYMOUS_USER)
+            });
+            // The file read, even if the path was replaced since.
+            let metadata = file.metadata()?;
+            if secret && metadata.permissions().mode() & 0o077 != 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "the .netrc file has passwords but is accessible by others",
+                ));
+            }
+
+            if secret && metadata.uid() != effective_uid()? {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "the .netrc file has passwords but is owned by another user",
+                ));
+            }
+        }
+
+        Ok(netrc)
+    }
+
+    /// Reads the file `NETRC` names, or else `.netrc` in the home
+    /// directory, if there is one.
+    pub fn load() -> std::io::Result<Option<Self>> {
+        let path = match std::env::var_os("NETRC") {
+            Some(path) => path.into(),
+            None => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".netrc"),
+                None => return Ok(None),
+            },
+        };
+
+        match Self::from_file(&path) {
+            Ok(netrc) => Ok(Some(netrc)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// The credentials for a machine. An entry without a login takes what
+    /// it lacks from the default entry, while one with a login only has
+    /// its own password and account.
+    pub fn credentials(&self, host: &str) -> Option<NetrcEntry> {
+        let entry = self.entry(host)?;
+        let default = self.entries.iter().find(|entry| entry.machine.is_none());
+        match default {
+            Some(default) if entry.login.is_none() => Some(NetrcEntry {
+                login: default.login.clone(),
+                password: entry.password.clone().or_else(|| default.password.clone()),
+                account: entry.account.clone().or_else(|| default.account.clone()),
+                ..entry.clone()
+            }),
+            _ => Some(entry.clone()),
+        }
+    }
+
+    /// The entry for a machine, or else the default entry.
+    pub fn entry(&self, host: &str) -> Option<&NetrcEntry> {
+        let machine = |entry: &&NetrcEntry| {
+            entry
+                .machine
+                .as_deref()
+                .is_some_and(|machine| machine.eq_ignore_ascii_case(host))
+        };
+
+        self.entries
+            .iter()
+            .find(machine)
+            .or_else(|| self.entries.iter().find(|entry| entry.machine.is_none()))
+    }
+}
+
+// Splits a `.netrc` file into words, which may be quoted with backslash
+// escapes, skipping comment lines. A `#` elsewhere, as in a password,
+// is part of a word.
+struct Tokens<'a> {
+    text: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl Tokens<'_> {
+    fn next(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let c = *self.text.get(self.position)?;
+            if c == b'#' && self.at_line_start() {
+                while self.peek().is_some_and(|c| c != b'\n') {
+                    self.position += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+
+        let quoted = self.peek() == Some(b'"');
+        if quoted {
+            self.position += 1;
+        }
+
+        let mut token = Vec::new();
+        while let Some(c) = self.peek() {
+            if (quoted && c == b'"') || (!quoted && c.is_ascii_whitespace()) {
+                break;
+            }
+
+            self.advance();
+            if c == b'\\' {
+                if let Some(escaped) = self.peek() {
+                    self.advance();
+                    token.push(escaped);
+                }
+            } else {
+                token.push(c);
+            }
+        }
+
+        if quoted {
+            self.position += 1;
+        }
+
+        Some(token)
+    }
+
+    fn value(&mut self) -> std::result::Result<Vec<u8>, NetrcError> {
+        self.next().ok_or(NetrcError {
+            line: self.line,
+            message: "expected a value",
+        })
+    }
+
+    // A macro's body starts on the next line and ends at an empty line.
+    fn macro_body(&mut self) -> Vec<u8> {
+        while self.peek().is_some_and(|c| c != b'\n') {
+            self.position += 1;
+        }
+        self.advance();
+
+        let start = self.position;
+        let mut end = start;
+        while end < self.text.len() {
+            let line_end = self.text[end..]
+                .iter()
+                .position(|&c| c == b'\n')
+                .map_or(self.text.len(), |index| end + index + 1);
+            if self.text[end..line_end].iter().all(u8::is_ascii_whitespace) {
+                break;
+            }
+
+            end = line_end;
+        }
+
+        while self.position < end {
+            self.advance();
+        }
+
+        self.text[start..end].to_vec()
+    }
+
+    // Whether only whitespace precedes the position on its line.
+    fn at_line_start(&self) -> bool {
+        let before = &self.text[..self.position];
+        before
+            .rsplit(|&c| c == b'\n')
+            .next()
+            .unwrap_or_default()
+            .iter()
+            .all(u8::is_ascii_whitespace)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn advance(&mut self) {
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+        }
+
+        self.position += 1;
+    }
+}
+
+impl Client {
+    /// Connects to a host and logs in with its `.netrc` credentials,
+    /// or anonymously if it has none.
+    pub fn connect_with_netrc(host: &str, port: u16, netrc: &Netrc) -> Result<Self> {
+        let mut client = Self::connect((host, port))?;
+        client.login_with_netrc(host, netrc)?;
+        Ok(client)
+    }
+
+    /// Logs in with the `.netrc` credentials for a host, as
+    /// `Netrc::credentials` tells them, or anonymously without a login.
+    pub fn login_with_netrc(&mut self, host: &str, netrc: &Netrc) -> Result<()> {
+        let entry = netrc
+            .credentials(host)
+            .filter(|entry| entry.login.is_some());
+        let Some(entry) = entry else {
+            return self.login(ANONYMOUS_USER, ANONYMOUS_PASSWORD);
+        };
+
+        let user = entry.login.as_deref().unwrap_or_default();
+        let password = match entry.password.as_deref() {
+            Some(password) => password,
+            None if user == ANONYMOUS_USER => ANONYMOUS_PASSWORD,
+            None => b"",
+        };
+        self.login_with_account(user, password, entry.account.as_deref())
+    }
+}
+
+// The effective user ID, without depending on libc. Linux tells it in
+// `/proc/self/status`, and elsewhere it owns the files the process creates.
+#[cfg(unix)]
+fn effective_uid() -> std::io::Result<u32> {
+    use std::os::unix::fs::MetadataExt;
+
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        let uid = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Uid:"))
+            .and_then(|ids| ids.split_whitespace().nth(1))
+            .and_then(|uid| uid.parse().ok());
+        if let Some(uid) = uid {
+            return Ok(uid);
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!("libftp-{}-uid", std::process::id()));
+    let uid = File::create(&path)?.metadata()?.uid();
+    let _ = std::fs::remove_file(path);
+    Ok(uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Netrc;
+    use crate::{test_server::TestServer, Client};
+
+    const NETRC: &[u8] = b"# Credentials
+machine ftp.example.com login user password \"p a\\\"ss\" account acct
+macdef init
+cd /pub
+binary
+
+machine EXAMPLE.org
+    login anonymous
+default login guest password guest
+";
+
+    #[test]
+    fn test_parse() {
+        let netrc = Netrc::parse(NETRC).unwrap();
+        assert_eq!(netrc.entries.len(), 3);
+
+        let entry = netrc.entry("ftp.example.com").unwrap();
+        assert_eq!(entry.login.as_deref(), Some(&b"user"[..]));
+        assert_eq!(entry.password.as_deref(), Some(&b"p a\"ss"[..]));
+        assert_eq!(entry.account.as_deref(), Some(&b"acct"[..]));
+        assert_eq!(entry.macros["init"], b"cd /pub\nbinary\n");
+
+        let entry = netrc.entry("example.org").unwrap();
+        assert_eq!(entry.login.as_deref(), Some(&b"anonymous"[..]));
+        assert_eq!(entry.password, None);
+
+        let entry = netrc.entry("other.example.com").unwrap();
+        assert_eq!(entry.machine, None);
+        assert_eq!(entry.login.as_deref(), Some(&b"guest"[..]));
+    }
+
+    #[test]
+    fn test_comments() {
+        let netrc = Netrc::parse(b"  # comment\nmachine host password #secret\n#login x").unwrap();
+        assert_eq!(netrc.entries.len(), 1);
+        assert_eq!(netrc.entries[0].password.as_deref(), Some(&b"#secret"[..]));
+        assert_eq!(netrc.entries[0].login, None);
+    }
+
+    #[test]
+    fn test_credentials() {
+        let netrc = Netrc::parse(
+            b"machine a password secret\nmachine b login bob\ndefault login guest password guest",
+        )
+        .unwrap();
+
+        let entry = netrc.credentials("a").unwrap();
+        assert_eq!(entry.machine.as_deref(), Some("a"));
+        assert_eq!(entry.login.as_deref(), Some(&b"guest"[..]));
+        assert_eq!(entry.password.as_deref(), Some(&b"secret"[..]));
+
+        let entry = netrc.credentials("b").unwrap();
+        assert_eq!(entry.login.as_deref(), Some(&b"bob"[..]));
+        assert_eq!(entry.password, None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = Netrc::parse(b"login user").unwrap_err();
+        assert_eq!(error.line, 1);
+
+        let error = Netrc::parse(b"machine host\nport 21").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        assert!(Netrc::parse(b"machine").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permissions() {
+        use std::{
+            fs::Permissions,
+            os::unix::fs::{MetadataExt, PermissionsExt},
+        };
+
+        let path = std::env::temp_dir().join(format!("libftp-{}-netrc", std::process::id()));
+        std::fs::write(&path, NETRC).unwrap();
+        // Files the process creates are owned by its effective user.
+        let owner = std::fs::metadata(&path).unwrap().uid();
+        assert_eq!(super::effective_uid().unwrap(), owner);
+
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        let error = Netrc::from_file(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+
+        std::fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        assert!(Netrc::from_file(&path).is_ok());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_connect_with_netrc() {
+        let server = TestServer::start();
+        let netrc = Netrc::parse(b"machine 127.0.0.1 login user password pass").unwrap();
+
+        let mut client =
+            Client::connect_with_netrc("127.0.0.1", server.address().port(), &netrc).unwrap();
+        client.mkdir(b"docs").unwrap();
+        assert_eq!(client.pwd().unwrap(), b"/");
+    }
+}
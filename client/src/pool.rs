@@ -0,0 +1,360 @@
+//! A pool of logged in sessions, saving the latency of connecting and
+//! logging in for every few commands sent to the same server.
+
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use libftp::command::Command;
+
+use crate::{Client, Result, TlsConfig};
+
+const DEFAULT_MAX_SIZE: usize = 4;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How a session is reset when returned to the pool, so whoever gets
+/// it next isn't affected by what it was used for.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum SessionReset {
+    /// `CWD /`, which keeps everything else such as the transfer type.
+    #[default]
+    ChangeToRoot,
+    /// `REIN`, then logging in again. Servers may end TLS on `REIN`,
+    /// so this suits unsecured sessions.
+    Reinitialize,
+}
+
+/// How sessions are pooled.
+#[derive(Clone, Debug)]
+pub struct PoolOptions {
+    /// The most sessions open at once for the same server and credentials.
+    /// Getting another one waits for one to be returned.
+    pub max_size: usize,
+    /// How long a session may stay unused in the pool before being closed.
+    pub idle_timeout: Duration,
+    pub reset: SessionReset,
+    /// Secures sessions with `AUTH TLS` before logging in.
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            reset: SessionReset::default(),
+            tls: None,
+        }
+    }
+}
+
+// The server and credentials sessions are pooled by, so a session is
+// only handed to whoever knows the password it logged in with.
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct PoolKey {
+    address: String,
+    user: Vec<u8>,
+    password: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    idle: Vec<(Client, Instant)>,
+    // Sessions counting towards `max_size`, idle or not.
+    open: usize,
+}
+
+/// Logged in sessions by server and credentials, which can be shared between threads.
+pub struct Pool {
+    options: PoolOptions,
+    sessions: Mutex<HashMap<PoolKey, Sessions>>,
+    returned: Condvar,
+}
+
+impl Pool {
+    pub fn new(options: PoolOptions) -> Self {
+        Self {
+            options,
+            sessions: Mutex::new(HashMap::new()),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Gets a session logged in as `user` on the server at `address`, such
+    /// as "ftp.example.com:21". Idle sessions are checked with `NOOP`
+    /// before being reused, and replaced if the server doesn't answer.
+    pub fn get(&self, address: &str, user: &[u8], password: &[u8]) -> Result<PooledClient<'_>> {
+        let key = PoolKey {
+            address: address.to_string(),
+            user: user.to_vec(),
+            password: password.to_vec(),
+        };
+
+        let idle = self
+            .checkout(&key)
+            .and_then(|mut client| client.command(&Command::Noop).is_ok().then_some(client));
+        let client = match idle {
+            Some(client) => client,
+            // The session that broke while idle keeps its room for a new one.
+            None => self.log_in(&key).inspect_err(|_| self.close(&key))?,
+        };
+
+        Ok(PooledClient {
+            pool: self,
+            key,
+            client: Some(client),
+            broken: false,
+        })
+    }
+
+    // Takes the most recently returned idle session, or else waits for
+    // room to open another one, which is counted as open already. Expired
+    // sessions are closed without logging out, not to hold the lock meanwhile.
+    fn checkout(&self, key: &PoolKey) -> Option<Client> {
+        let mut sessions = self.sessions.lock().unwrap();
+        loop {
+            let entry = sessions.entry(key.clone()).or_default();
+            let now = Instant::now();
+            let count = entry.idle.len();
+            entry
+                .idle
+                .retain(|(_, since)| now.duration_since(*since) < self.options.idle_timeout);
+            entry.open -= count - entry.idle.len();
+
+            if let Some((client, _)) = entry.idle.pop() {
+                return Some(client);
+            }
+
+            if entry.open < self.options.max_size.max(1) {
+                entry.open += 1;
+                return None;
+            }
+
+            sessions = self.returned.wait(sessions).unwrap();
+        }
+    }
+
+    fn log_in(&self, key: &PoolKey) -> Result<Client> {
+        let mut client = Client::connect(&key.address)?;
+        if let Some(tls) = &self.options.tls {
+            client.auth_tls(tls.clone())?;
+        }
+
+        client.login(&key.user, &key.password)?;
+        Ok(client)
+    }
+
+    // Returns a session to the pool once reset, or closes it if it can't be.
+    fn give_back(&self, key: PoolKey, mut client: Client) {
+        if client.is_broken() {
+            return self.close(&key);
+        }
+
+        let reset = match self.options.reset {
+            SessionReset::ChangeToRoot => client.cwd(b"/"),
+            SessionReset::Reinitialize => client
+                .reinitialize()
+                .and_then(|()| client.login(&key.user, &key.password)),
+        };
+        if reset.is_err() {
+            return self.close(&key);
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let entry = sessions.entry(key).or_default();
+        entry.idle.push((client, Instant::now()));
+        // Waiters for every server share the condition variable.
+        self.returned.notify_all();
+    }
+
+    // Makes room for another session after one was closed.
+    fn close(&self, key: &PoolKey) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(entry) = sessions.get_mut(key) {
+            entry.open -= 1;
+        }
+
+        self.returned.notify_all();
+    }
+}
+
+/// A session out of the pool, which returns to it when dropped, unless
+/// it's broken.
+pub struct PooledClient<'a> {
+    pool: &'a Pool,
+    key: PoolKey,
+    client: Option<Client>,
+    broken: bool,
+}
+
+impl PooledClient<'_> {
+    /// Has the session closed when dropped instead of returned to the pool,
+    /// such as after a reply it didn't expect. Sessions whose replies are
+    /// out of step, as told by `Client::is_broken`, are closed anyway.
+    pub fn mark_broken(&mut self) {
+        self.broken = true;
+    }
+
+    /// Closes the session instead of returning it to the pool.
+    pub fn close(mut self) -> Result<()> {
+        let client = self.client.take().expect("a pooled client");
+        self.pool.close(&self.key);
+        client.quit()
+    }
+}
+
+impl Deref for PooledClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().expect("a pooled client")
+    }
+}
+
+impl DerefMut for PooledClient<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().expect("a pooled client")
+    }
+}
+
+impl Drop for PooledClient<'_> {
+    fn drop(&mut self) {
+        match self.client.take() {
+            Some(_) if self.broken => self.pool.close(&self.key),
+            Some(client) => self.pool.give_back(self.key.clone(), client),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Shutdown,
+        sync::atomic::{AtomicBool, Ordering},
+        thread::{scope, sleep},
+        time::Duration,
+    };
+
+    use libftp::command::Command;
+
+    use super::{Pool, PoolOptions, SessionReset};
+    use crate::test_server::TestServer;
+
+    fn pool(reset: SessionReset) -> Pool {
+        Pool::new(PoolOptions {
+            max_size: 1,
+            reset,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_reuse() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        for reset in [SessionReset::ChangeToRoot, SessionReset::Reinitialize] {
+            let pool = pool(reset);
+            let mut client = pool.get(&address, b"user", b"pass").unwrap();
+            let local = client.control_stream().local_addr().unwrap();
+            client.mkdir(b"docs").unwrap();
+            client.cwd(b"docs").unwrap();
+            drop(client);
+
+            let mut client = pool.get(&address, b"user", b"pass").unwrap();
+            assert_eq!(client.control_stream().local_addr().unwrap(), local);
+            assert_eq!(client.pwd().unwrap(), b"/");
+        }
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        let pool = Pool::new(PoolOptions::default());
+
+        drop(pool.get(&address, b"user", b"pass").unwrap());
+        assert!(pool.get(&address, b"user", b"wrong").is_err());
+        assert!(pool.get(&address, b"user", b"pass").is_ok());
+    }
+
+    #[test]
+    fn test_idle_expiry() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        let pool = Pool::new(PoolOptions {
+            idle_timeout: Duration::ZERO,
+            ..Default::default()
+        });
+
+        let client = pool.get(&address, b"user", b"pass").unwrap();
+        let local = client.control_stream().local_addr().unwrap();
+        drop(client);
+
+        let client = pool.get(&address, b"user", b"pass").unwrap();
+        assert_ne!(client.control_stream().local_addr().unwrap(), local);
+    }
+
+    #[test]
+    fn test_broken_session() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        let pool = pool(SessionReset::ChangeToRoot);
+
+        // The server closes the connection, so the session can't be reset.
+        let mut client = pool.get(&address, b"user", b"pass").unwrap();
+        client.execute(&Command::Logout).unwrap();
+        drop(client);
+
+        let mut client = pool.get(&address, b"user", b"pass").unwrap();
+        assert_eq!(client.pwd().unwrap(), b"/");
+    }
+
+    #[test]
+    fn test_closing_broken_sessions() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        let pool = pool(SessionReset::ChangeToRoot);
+
+        let mut client = pool.get(&address, b"user", b"pass").unwrap();
+        let local = client.control_stream().local_addr().unwrap();
+        client.mark_broken();
+        drop(client);
+
+        // A session failing on its control connection is broken too.
+        let mut client = pool.get(&address, b"user", b"pass").unwrap();
+        assert_ne!(client.control_stream().local_addr().unwrap(), local);
+        let local = client.control_stream().local_addr().unwrap();
+        client.control_stream().shutdown(Shutdown::Write).unwrap();
+        assert!(client.pwd().is_err());
+        assert!(client.is_broken());
+        drop(client);
+
+        let mut client = pool.get(&address, b"user", b"pass").unwrap();
+        assert_ne!(client.control_stream().local_addr().unwrap(), local);
+        assert_eq!(client.pwd().unwrap(), b"/");
+    }
+
+    #[test]
+    fn test_max_size() {
+        let server = TestServer::start();
+        let address = server.address().to_string();
+        let pool = pool(SessionReset::ChangeToRoot);
+        let returned = AtomicBool::new(false);
+
+        let client = pool.get(&address, b"user", b"pass").unwrap();
+        scope(|scope| {
+            scope.spawn(|| {
+                let _client = pool.get(&address, b"user", b"pass").unwrap();
+                assert!(returned.load(Ordering::SeqCst));
+            });
+
+            sleep(Duration::from_millis(50));
+            returned.store(true, Ordering::SeqCst);
+            drop(client);
+        });
+    }
+}
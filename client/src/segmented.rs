@@ -0,0 +1,189 @@
+//! Downloading a file in segments over several connections at once,
+//! which is faster than a single stream over high-latency links.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+    thread::scope,
+};
+
+use libftp::{command::Command, reply::Reply};
+
+use crate::{Client, Result};
+
+// Smaller segments aren't worth another connection.
+const MIN_SEGMENT_SIZE: u64 = 64 * 1024;
+
+/// Downloads the file at `path` into the local file at `local`, split into
+/// up to `segments` ranges downloaded at once. Each range is downloaded
+/// over a client `connect` opens and logs in, with `REST` and `RETR`,
+/// aborting the transfer with `ABOR` at the end of the range. The local file
+/// is sized up front, which leaves it sparse until every range is written.
+///
+/// Falls back to a single stream over the first client when the server
+/// doesn't tell the size of the file or advertise `REST STREAM` with
+/// `FEAT`, or the file is too small to split, or when a segment is refused
+/// with a permanent negative reply, such as to a `REST` the server doesn't
+/// implement after all. Servers without `FEAT` are never known to implement
+/// `REST STREAM`, so their files are always downloaded in a single stream.
+/// Returns the amount of bytes written.
+pub fn segmented_retr<F>(connect: F, path: &[u8], local: &Path, segments: usize) -> Result<u64>
+where
+    F: Fn() -> Result<Client> + Sync,
+{
+    let mut client = connect()?;
+    client.require_image_type()?;
+    let size = match client.size(path) {
+        Ok(size) => size,
+        Err(error) if error.reply().is_some() => 0,
+        Err(error) => return Err(error),
+    };
+    let segments = segments.min(usize::try_from(size / MIN_SEGMENT_SIZE).unwrap_or(usize::MAX));
+    if segments <= 1 || !implements_restart(&mut client)? {
+        return client.retr(path, &mut File::create(local)?);
+    }
+
+    File::create(local)?.set_len(size)?;
+    let mut ranges = split(size, segments).into_iter();
+    let first = ranges.next().expect("at least two segments");
+    let connect = &connect;
+    let segmented = scope(|scope| {
+        let others: Vec<_> = ranges
+            .map(|range| {
+                scope.spawn(move || {
+                    let mut client = connect()?;
+                    client.require_image_type()?;
+                    retrieve_range(&mut client, path, local, range, size)
+                })
+            })
+            .collect();
+
+        let first = retrieve_range(&mut client, path, local, first, size);
+        others
+            .into_iter()
+            .map(|segment| segment.join().expect("a segment panicked"))
+            .chain([first])
+            .collect::<Result<()>>()
+    });
+
+    match segmented {
+        Err(error) if error.reply().is_some_and(Reply::is_permanent_negative) => {
+            client.retr(path, &mut File::create(local)?)
+        }
+        result => result.map(|()| size),
+    }
+}
+
+// Whether the server implements `REST` for stream mode according to RFC 3659.
+// Probing with `REST` instead would leave it pending until the next command,
+// which must be the transfer it applies to.
+fn implements_restart(client: &mut Client) -> Result<bool> {
+    let features = client.features()?;
+    Ok(features
+        .iter()
+        .any(|feature| feature.eq_ignore_ascii_case(b"REST STREAM")))
+}
+
+// Splits `size` bytes into `segments` ranges of about the same length.
+fn split(size: u64, segments: usize) -> Vec<Range<u64>> {
+    let segments = segments as u64;
+    (0..segments)
+        .map(|index| size * index / segments..size * (index + 1) / segments)
+        .collect()
+}
+
+fn retrieve_range(
+    client: &mut Client,
+    path: &[u8],
+    local: &Path,
+    range: Range<u64>,
+    size: u64,
+) -> Result<()> {
+    let mut file = OpenOptions::new().write(true).open(local)?;
+    file.seek(SeekFrom::Start(range.start))?;
+
+    let data = client.start_transfer_from(&Command::Retrieve(path.to_vec()), range.start)?;
+    let length = range.end - range.start;
+    let mut limited = data.take(length);
+    let copied = std::io::copy(&mut limited, &mut file).and_then(|copied| {
+        if copied != length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the data connection closed before the end of the segment",
+            ));
+        }
+
+        Ok(())
+    });
+
+    let data = limited.into_inner();
+    if let Err(error) = copied {
+        drop(data);
+        client.abort_failed_transfer();
+        return Err(error.into());
+    }
+
+    if range.end == size {
+        drop(data);
+        client.end_transfer()
+    } else {
+        client.abort_transfer(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{segmented_retr, split};
+    use crate::{test_server::TestServer, Client, Result};
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("libftp-{}-{name}", std::process::id()))
+    }
+
+    fn download(server: &TestServer, local: &Path) -> Result<u64> {
+        let connect = || {
+            let mut client = Client::connect(server.address())?;
+            client.login(b"user", b"pass")?;
+            Ok(client)
+        };
+        segmented_retr(connect, b"big.bin", local, 4)
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(10, 3), [0..3, 3..6, 6..10]);
+    }
+
+    #[test]
+    fn test_segmented_retr() {
+        let server = TestServer::start();
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        server.put_file(b"/big.bin", &data, Default::default());
+
+        let local = temp_file("segmented");
+        assert_eq!(download(&server, &local).unwrap(), 1_000_000);
+        assert_eq!(std::fs::read(&local).unwrap(), data);
+        std::fs::remove_file(local).unwrap();
+    }
+
+    #[test]
+    fn test_single_stream_fallback() {
+        // Without `FEAT`, the server isn't known to implement `REST`, and
+        // without `SIZE`, the file can't be split. Segments would fail on
+        // the refused `REST`, which is also refused despite `FEAT`.
+        for refused in [&["FEAT", "REST"][..], &["SIZE", "REST"], &["REST"]] {
+            let server = TestServer::refusing(refused);
+            let data = vec![7; 500_000];
+            server.put_file(b"/big.bin", &data, Default::default());
+
+            let local = temp_file(&format!("single_stream_{}", refused.join("_")));
+            assert_eq!(download(&server, &local).unwrap(), 500_000);
+            assert_eq!(std::fs::read(&local).unwrap(), data);
+            std::fs::remove_file(local).unwrap();
+        }
+    }
+}
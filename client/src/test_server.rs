@@ -0,0 +1,646 @@
+//! A small in-memory FTP server to test the client against, accepting
+//! user "user" with password "pass", and user "account" which also
+//! needs an account. Commands can be refused as unrecognized, to test
+//! falling back to others. With TLS, data connections must resume the
+//! TLS session of the control connection, as vsftpd requires.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread::spawn,
+    time::{Duration, SystemTime},
+};
+
+use libftp::{
+    command::{Command, ProtectionLevel},
+    listing::{format_mlsd_line, format_unix_line, DateTime, EntryKind, Fact, ListEntry},
+    parser::parse_command,
+    reply::Reply,
+    serializer::{ReplySerializer, Serializer},
+};
+use rustls::{
+    pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
+    ClientConfig, HandshakeKind, RootCertStore, ServerConfig, ServerConnection,
+};
+
+use crate::{tls::Stream, TlsConfig};
+
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct FileSystem {
+    files: BTreeMap<Vec<u8>, Vec<u8>>,
+    modified: BTreeMap<Vec<u8>, DateTime>,
+    directories: BTreeSet<Vec<u8>>,
+    // Entries listed in a directory without anything backing them.
+    forged: BTreeMap<Vec<u8>, Vec<ListEntry>>,
+}
+
+impl FileSystem {
+    fn store(&mut self, path: Vec<u8>, data: Vec<u8>, modified: DateTime) {
+        self.modified.insert(path.clone(), modified);
+        self.files.insert(path, data);
+    }
+
+    fn remove(&mut self, path: &[u8]) -> Option<Vec<u8>> {
+        self.modified.remove(path);
+        self.files.remove(path)
+    }
+
+    // The directories, then the files in a directory. Directories don't
+    // keep a modification time, so they were all modified `now`.
+    fn entries(&self, directory: &[u8], now: DateTime) -> Vec<ListEntry> {
+        let directories = self.directories.iter().filter_map(|path| {
+            Some(ListEntry {
+                name: child_name(path, directory)?.to_vec(),
+                kind: EntryKind::Directory,
+                modified: Some(now),
+                ..Default::default()
+            })
+        });
+        let files = self.files.iter().filter_map(|(path, data)| {
+            Some(ListEntry {
+                name: child_name(path, directory)?.to_vec(),
+                kind: EntryKind::File,
+                size: Some(data.len() as u64),
+                modified: self.modified.get(path).copied(),
+                ..Default::default()
+            })
+        });
+
+        let forged = self.forged.get(directory).into_iter().flatten().cloned();
+        directories.chain(files).chain(forged).collect()
+    }
+}
+
+pub(crate) struct TestServer {
+    address: SocketAddr,
+    file_system: Arc<Mutex<FileSystem>>,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        Self::launch(Behaviour::default())
+    }
+
+    /// Starts a server replying 500 to the commands named `refused`.
+    pub fn refusing(refused: &[&str]) -> Self {
+        Self::launch(Behaviour {
+            refused: refused
+                .iter()
+                .map(|name| name.as_bytes().to_vec())
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Starts a server sending `ip` in 227 replies, as servers behind a NAT do,
+    /// while listening on localhost.
+    pub fn advertising(ip: Ipv4Addr) -> Self {
+        Self::launch(Behaviour {
+            passive_ip: ip,
+            ..Default::default()
+        })
+    }
+
+    /// Starts a server with a self-signed certificate for "localhost",
+    /// returning it with the TLS configuration of clients trusting it.
+    /// Implicit FTPS secures connections before greeting the client.
+    pub fn with_tls(implicit: bool) -> (Self, TlsConfig) {
+        Self::launch_tls(Behaviour {
+            implicit,
+            ..Default::default()
+        })
+    }
+
+    /// Starts a server like `with_tls`, which keeps data connections
+    /// open after uploads instead of closing them after the client.
+    pub fn with_lingering_tls() -> (Self, TlsConfig) {
+        Self::launch_tls(Behaviour {
+            lingering: true,
+            ..Default::default()
+        })
+    }
+
+    fn launch_tls(behaviour: Behaviour) -> (Self, TlsConfig) {
+        let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let certificate = certified.cert.der().clone();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+            certified.signing_key.serialize_der(),
+        ));
+        let server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate.clone()], key)
+            .unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate).unwrap();
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let server = Self::launch(Behaviour {
+            tls: Some(Arc::new(server_config)),
+            ..behaviour
+        });
+        let tls = TlsConfig::new(
+            Arc::new(client_config),
+            ServerName::try_from("localhost").unwrap(),
+        );
+        (server, tls)
+    }
+
+    fn launch(behaviour: Behaviour) -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let file_system = Arc::new(Mutex::new(FileSystem::default()));
+        file_system
+            .lock()
+            .unwrap()
+            .directories
+            .insert(b"/".to_vec());
+
+        let shared = file_system.clone();
+        spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    break;
+                };
+
+                let file_system = shared.clone();
+                let behaviour = behaviour.clone();
+                spawn(move || Session::new(stream, file_system, behaviour).run());
+            }
+        });
+
+        Self {
+            address,
+            file_system,
+        }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn file(&self, path: &[u8]) -> Option<Vec<u8>> {
+        self.file_system.lock().unwrap().files.get(path).cloned()
+    }
+
+    /// Lists `entry` in `directory` from now on, whatever its name,
+    /// as broken or malicious servers might.
+    pub fn forge_entry(&self, directory: &[u8], entry: ListEntry) {
+        let mut file_system = self.file_system.lock().unwrap();
+        file_system
+            .forged
+            .entry(directory.to_vec())
+            .or_default()
+            .push(entry);
+    }
+
+    pub fn put_file(&self, path: &[u8], data: &[u8], modified: DateTime) {
+        let mut file_system = self.file_system.lock().unwrap();
+        file_system.store(path.to_vec(), data.to_vec(), modified);
+    }
+}
+
+#[derive(Clone)]
+struct Behaviour {
+    refused: Vec<Vec<u8>>,
+    passive_ip: Ipv4Addr,
+    tls: Option<Arc<ServerConfig>>,
+    implicit: bool,
+    lingering: bool,
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Self {
+            refused: Vec::new(),
+            passive_ip: Ipv4Addr::LOCALHOST,
+            tls: None,
+            implicit: false,
+            lingering: false,
+        }
+    }
+}
+
+// How the next data connection is opened.
+enum DataSource {
+    Listening(TcpListener),
+    Connecting(SocketAddr),
+}
+
+struct Session {
+    stream: Stream,
+    // Received bytes not read as a command yet.
+    incoming: Vec<u8>,
+    file_system: Arc<Mutex<FileSystem>>,
+    behaviour: Behaviour,
+    user: Option<Vec<u8>>,
+    logged_in: bool,
+    directory: Vec<u8>,
+    rename_from: Option<Vec<u8>>,
+    // The offset set by `REST` for the next transfer.
+    restart: Option<usize>,
+    data: Option<DataSource>,
+    protect_data: bool,
+    // Data connections kept open while lingering.
+    lingering: Vec<Stream>,
+}
+
+impl Session {
+    fn new(stream: TcpStream, file_system: Arc<Mutex<FileSystem>>, behaviour: Behaviour) -> Self {
+        Self {
+            stream: Stream::new(stream),
+            incoming: Vec::new(),
+            file_system,
+            behaviour,
+            user: None,
+            logged_in: false,
+            directory: b"/".to_vec(),
+            rename_from: None,
+            restart: None,
+            data: None,
+            protect_data: false,
+            lingering: Vec::new(),
+        }
+    }
+
+    fn run(mut self) {
+        // The client may hang up at any time.
+        let _ = self.serve();
+    }
+
+    fn serve(&mut self) -> std::io::Result<()> {
+        if self.behaviour.implicit {
+            self.secure()?;
+        }
+
+        self.reply(Reply::service_ready())?;
+        while let Some(line) = self.read_line()? {
+            let name = line
+                .split(|&c| c == b' ' || c == b'\r')
+                .next()
+                .unwrap_or(b"");
+            if self
+                .behaviour
+                .refused
+                .iter()
+                .any(|refused| refused.eq_ignore_ascii_case(name))
+            {
+                self.reply(Reply::command_unrecognized())?;
+                continue;
+            }
+
+            let reply = match parse_command(&line) {
+                Ok((_, Command::Logout)) => return self.reply(Reply::service_closing()),
+                Ok((_, Command::Authentication(mechanism)))
+                    if self.behaviour.tls.is_some() && mechanism.eq_ignore_ascii_case(b"TLS") =>
+                {
+                    self.reply(Reply::security_exchange_complete())?;
+                    self.secure()?;
+                    continue;
+                }
+                Ok((_, Command::ClearCommandChannel)) => {
+                    self.reply(Reply::command_okay())?;
+                    self.stream.unsecure()?;
+                    continue;
+                }
+                Ok((_, command)) => self.handle(command)?,
+                Err(_) => Reply::command_unrecognized(),
+            };
+            self.reply(reply)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(end) = self.incoming.iter().position(|&c| c == b'\n') {
+                return Ok(Some(self.incoming.drain(..=end).collect()));
+            }
+
+            let mut input = [0u8; 1024];
+            let read = self.stream.read(&mut input)?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            self.incoming.extend_from_slice(&input[..read]);
+        }
+    }
+
+    fn reply(&mut self, reply: Reply) -> std::io::Result<()> {
+        let mut serialized = Vec::new();
+        ReplySerializer::new(&mut serialized).serialize(&reply)?;
+        self.stream.write_all(&serialized)?;
+        self.stream.flush()
+    }
+
+    fn secure(&mut self) -> std::io::Result<()> {
+        let config = self.behaviour.tls.clone().expect("a TLS configuration");
+        let connection = ServerConnection::new(config).map_err(std::io::Error::other)?;
+        self.stream.secure(connection)
+    }
+
+    fn handle(&mut self, command: Command) -> std::io::Result<Reply> {
+        match command {
+            Command::Reinitialize => {
+                self.user = None;
+                self.logged_in = false;
+                self.directory = b"/".to_vec();
+                return Ok(Reply::service_ready());
+            }
+            Command::UserName(user) => {
+                self.logged_in = false;
+                self.user = Some(user);
+                return Ok(Reply::need_password());
+            }
+            Command::Password(password) => {
+                return Ok(match (self.user.as_deref(), &password[..]) {
+                    (Some(b"user"), b"pass") => {
+                        self.logged_in = true;
+                        Reply::user_logged_in()
+                    }
+                    (Some(b"account"), b"pass") => Reply::need_account_for_login(),
+                    _ => Reply::not_logged_in(),
+                });
+            }
+            Command::Account(_) if self.user.as_deref() == Some(b"account") => {
+                self.logged_in = true;
+                return Ok(Reply::user_logged_in());
+            }
+            Command::ProtectionBufferSize(_) => return Ok(Reply::command_okay()),
+            Command::Features => {
+                let features = [
+                    b" MDTM".to_vec(),
+                    b" SIZE".to_vec(),
+                    b" REST STREAM".to_vec(),
+                    [b" ", &Fact::feature_line(&Fact::ALL)[..]].concat(),
+                ];
+                return Ok(Reply::builder(*b"211")
+                    .line("Extensions supported:")
+                    .lines(features)
+                    .last_line("End"));
+            }
+            Command::DataChannelProtection(level) => {
+                self.protect_data = level == ProtectionLevel::Private;
+                return Ok(Reply::command_okay());
+            }
+            _ if !self.logged_in => return Ok(Reply::not_logged_in()),
+            _ => {}
+        }
+
+        // How `LIST`, `NLST` and `MLSD` format entries.
+        let format_entry: fn(&ListEntry, DateTime) -> Vec<u8> = match command {
+            Command::List(_) => format_unix_line,
+            Command::MachineListDirectory(_) => |entry, _| format_mlsd_line(entry, &Fact::ALL),
+            _ => |entry, _| entry.name.clone(),
+        };
+        let file_system = self.file_system.clone();
+        let mut file_system = file_system.lock().unwrap();
+        Ok(match command {
+            Command::PrintWorkingDirectory => Reply::current_directory(&self.directory),
+            Command::ChangeWorkingDirectory(path) => {
+                let path = self.resolve(&path);
+                if file_system.directories.contains(&path) {
+                    self.directory = path;
+                    Reply::file_action_okay()
+                } else {
+                    Reply::action_not_taken()
+                }
+            }
+            Command::MakeDirectory(path) => {
+                let path = self.resolve(&path);
+                file_system.directories.insert(path.clone());
+                Reply::pathname_created(&path)
+            }
+            Command::RemoveDirectory(path) => {
+                let path = self.resolve(&path);
+                let empty = file_system.entries(&path, DateTime::default()).is_empty();
+                if path != b"/" && empty && file_system.directories.remove(&path) {
+                    Reply::file_action_okay()
+                } else {
+                    Reply::action_not_taken()
+                }
+            }
+            Command::Delete(path) => match file_system.remove(&self.resolve(&path)) {
+                Some(_) => Reply::file_action_okay(),
+                None => Reply::action_not_taken(),
+            },
+            Command::RenameFrom(path) => {
+                let path = self.resolve(&path);
+                if file_system.files.contains_key(&path) {
+                    self.rename_from = Some(path);
+                    Reply::file_action_pending()
+                } else {
+                    Reply::action_not_taken()
+                }
+            }
+            Command::RenameTo(path) => match self.rename_from.take() {
+                Some(from) => {
+                    let modified = file_system.modified.get(&from).copied().unwrap_or_default();
+                    let data = file_system.remove(&from).unwrap_or_default();
+                    file_system.store(self.resolve(&path), data, modified);
+                    Reply::file_action_okay()
+                }
+                None => Reply::bad_sequence_of_commands(),
+            },
+            Command::RepresentationType(_) => Reply::command_okay(),
+            Command::Passive => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                let port = listener.local_addr()?.port();
+                self.data = Some(DataSource::Listening(listener));
+                Reply::entering_passive_mode(SocketAddrV4::new(self.behaviour.passive_ip, port))
+            }
+            Command::ExtendedPassive(_) => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                let port = listener.local_addr()?.port();
+                self.data = Some(DataSource::Listening(listener));
+                Reply::entering_extended_passive_mode(port)
+            }
+            Command::DataPort(ip, port) => {
+                self.data = Some(DataSource::Connecting(SocketAddr::from((ip, port))));
+                Reply::command_okay()
+            }
+            Command::ExtendedDataPort(address) => {
+                self.data = Some(DataSource::Connecting(address));
+                Reply::command_okay()
+            }
+            Command::List(path) | Command::NameList(path) | Command::MachineListDirectory(path)
+                if self.data.is_some() =>
+            {
+                let directory = self.resolve(path.as_deref().unwrap_or(b"."));
+                let now = DateTime::from(SystemTime::now());
+                let mut listing = Vec::new();
+                for entry in file_system.entries(&directory, now) {
+                    listing.extend_from_slice(&format_entry(&entry, now));
+                    listing.extend_from_slice(b"\r\n");
+                }
+
+                drop(file_system);
+                self.send(&listing)?
+            }
+            Command::FileSize(path) => match file_system.files.get(&self.resolve(&path)) {
+                Some(data) => Reply::file_status(data.len().to_string()),
+                None => Reply::action_not_taken(),
+            },
+            Command::ModificationTime(path) => {
+                match file_system.modified.get(&self.resolve(&path)) {
+                    Some(modified) => Reply::file_status(modified.time_val()),
+                    None => Reply::action_not_taken(),
+                }
+            }
+            Command::Restart(marker) => match std::str::from_utf8(&marker).map(str::parse) {
+                Ok(Ok(offset)) => {
+                    self.restart = Some(offset);
+                    Reply::file_action_pending()
+                }
+                _ => Reply::syntax_error_in_parameters(),
+            },
+            Command::Retrieve(path) if self.data.is_some() => {
+                match file_system.files.get(&self.resolve(&path)).cloned() {
+                    Some(data) => {
+                        drop(file_system);
+                        let offset = self.restart.take().unwrap_or(0).min(data.len());
+                        self.send(&data[offset..])?
+                    }
+                    None => Reply::action_not_taken(),
+                }
+            }
+            Command::Store(path) if self.data.is_some() => {
+                drop(file_system);
+                let path = self.resolve(&path);
+                let mut data = match self.restart.take() {
+                    Some(offset) => {
+                        let file_system = self.file_system.lock().unwrap();
+                        let mut data = file_system.files.get(&path).cloned().unwrap_or_default();
+                        data.truncate(offset);
+                        data
+                    }
+                    None => Vec::new(),
+                };
+                self.reply(Reply::file_status_okay())?;
+                let received = self.accept().and_then(|mut stream| {
+                    stream.read_to_end(&mut data)?;
+                    if self.behaviour.lingering {
+                        self.lingering.push(stream);
+                    }
+
+                    Ok(())
+                });
+                match received {
+                    Ok(()) => {
+                        let now = DateTime::from(SystemTime::now());
+                        self.file_system.lock().unwrap().store(path, data, now);
+                        Reply::closing_data_connection()
+                    }
+                    Err(_) => Reply::transfer_aborted(),
+                }
+            }
+            Command::List(_)
+            | Command::NameList(_)
+            | Command::MachineListDirectory(_)
+            | Command::Retrieve(_)
+            | Command::Store(_) => Reply::cant_open_data_connection(),
+            // Transfers are over by the time the next command is read.
+            Command::Abort => Reply::closing_data_connection(),
+            Command::Noop => Reply::command_okay(),
+            _ => Reply::command_not_implemented(),
+        })
+    }
+
+    fn accept(&mut self) -> std::io::Result<Stream> {
+        let socket = match self.data.take().expect("a data source") {
+            DataSource::Listening(listener) => listener.accept()?.0,
+            DataSource::Connecting(address) => TcpStream::connect(address)?,
+        };
+
+        let mut stream = Stream::new(socket);
+        if self.protect_data {
+            self.secure_data(&mut stream)?;
+        }
+
+        Ok(stream)
+    }
+
+    fn secure_data(&self, stream: &mut Stream) -> std::io::Result<()> {
+        let config = self.behaviour.tls.clone().expect("a TLS configuration");
+        let connection = ServerConnection::new(config).map_err(std::io::Error::other)?;
+        stream.secure(connection)?;
+
+        let resumed = stream
+            .tls()
+            .is_some_and(|tls| tls.handshake_kind() == Some(HandshakeKind::Resumed));
+        if !resumed {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "the data connection didn't resume the TLS session",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, data: &[u8]) -> std::io::Result<Reply> {
+        self.reply(Reply::file_status_okay())?;
+        match self.accept().and_then(|mut stream| {
+            stream
+                .write_all(data)
+                .and_then(|()| stream.close(CLOSE_TIMEOUT))
+        }) {
+            Ok(()) => Ok(Reply::closing_data_connection()),
+            // The client closed the data connection before receiving it all,
+            // or didn't resume the TLS session.
+            Err(_) => Ok(Reply::transfer_aborted()),
+        }
+    }
+
+    fn resolve(&self, path: &[u8]) -> Vec<u8> {
+        let mut components: Vec<&[u8]> = Vec::new();
+        let relative_to: &[u8] = if path.starts_with(b"/") {
+            b""
+        } else {
+            &self.directory
+        };
+        for component in relative_to
+            .split(|&c| c == b'/')
+            .chain(path.split(|&c| c == b'/'))
+        {
+            match component {
+                b"" | b"." => {}
+                b".." => {
+                    components.pop();
+                }
+                component => components.push(component),
+            }
+        }
+
+        let mut resolved = Vec::new();
+        for component in components {
+            resolved.push(b'/');
+            resolved.extend_from_slice(component);
+        }
+
+        if resolved.is_empty() {
+            resolved.push(b'/');
+        }
+
+        resolved
+    }
+}
+
+// The names and contents of the files directly inside a directory.
+// The name of a file or directory at `path` if it's in `directory`.
+fn child_name<'a>(path: &'a [u8], directory: &[u8]) -> Option<&'a [u8]> {
+    let name = match directory {
+        b"/" => path.strip_prefix(b"/")?,
+        _ => path.strip_prefix(directory)?.strip_prefix(b"/")?,
+    };
+
+    (!name.is_empty() && !name.contains(&b'/')).then_some(name)
+}
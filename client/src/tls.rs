@@ -0,0 +1,184 @@
+//! FTP over TLS according to RFC 4217.
+
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, Connection};
+
+/// The port servers listen on for implicit FTPS, where connections
+/// are secured before the server greets the client.
+pub const IMPLICIT_TLS_PORT: u16 = 990;
+
+// Length of a TLS record header, ending with the length of the record.
+const RECORD_HEADER_SIZE: usize = 5;
+
+/// How connections to a server are secured with TLS. Data connections
+/// resume the TLS session of the control connection through the session
+/// cache of `config`, as servers such as vsftpd require.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    config: Arc<ClientConfig>,
+    server_name: ServerName<'static>,
+}
+
+impl TlsConfig {
+    pub fn new(config: Arc<ClientConfig>, server_name: ServerName<'static>) -> Self {
+        Self {
+            config,
+            server_name,
+        }
+    }
+
+    pub(crate) fn connection(&self) -> std::io::Result<ClientConnection> {
+        ClientConnection::new(self.config.clone(), self.server_name.clone())
+            .map_err(std::io::Error::other)
+    }
+}
+
+/// A connection, which is secured by TLS once a TLS session is started over it.
+pub(crate) struct Stream {
+    socket: TcpStream,
+    tls: Option<Connection>,
+}
+
+impl Stream {
+    pub fn new(socket: TcpStream) -> Self {
+        Self { socket, tls: None }
+    }
+
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    #[cfg(test)]
+    pub fn tls(&self) -> Option<&Connection> {
+        self.tls.as_ref()
+    }
+
+    /// Starts a TLS session, handshaking right away.
+    pub fn secure(&mut self, connection: impl Into<Connection>) -> std::io::Result<()> {
+        let mut tls = connection.into();
+        while tls.is_handshaking() {
+            tls.complete_io(&mut self.socket)?;
+        }
+
+        self.tls = Some(tls);
+        self.flush()
+    }
+
+    /// Ends the TLS session, keeping the connection open to be used
+    /// unsecured, after both peers sent their close notification.
+    pub fn unsecure(&mut self) -> std::io::Result<()> {
+        let Some(tls) = &mut self.tls else {
+            return Ok(());
+        };
+
+        tls.send_close_notify();
+        while tls.wants_write() {
+            tls.write_tls(&mut self.socket)?;
+        }
+
+        let mut discarded = [0u8; 1024];
+        loop {
+            match tls.reader().read(&mut discarded) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    // Whatever follows the peer's close notification is unsecured,
+                    // so records are read one at a time not to read past it.
+                    let mut record = vec![0u8; RECORD_HEADER_SIZE];
+                    self.socket.read_exact(&mut record)?;
+                    let length = u16::from_be_bytes([record[3], record[4]]);
+                    record.resize(RECORD_HEADER_SIZE + usize::from(length), 0);
+                    self.socket.read_exact(&mut record[RECORD_HEADER_SIZE..])?;
+
+                    tls.read_tls(&mut &record[..])?;
+                    tls.process_new_packets().map_err(std::io::Error::other)?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.tls = None;
+        Ok(())
+    }
+
+    /// Closes the connection, ending the TLS session first, which tells
+    /// the peer no data was cut off. The peer is given `timeout` to close.
+    pub fn close(mut self, timeout: Duration) -> std::io::Result<()> {
+        let Some(tls) = &mut self.tls else {
+            return self.flush();
+        };
+
+        tls.send_close_notify();
+        self.flush()?;
+
+        // Closing with records left unread, such as session tickets, resets
+        // the connection, which can drop the end of the data before the peer
+        // reads it. So the peer closes first.
+        self.socket.shutdown(Shutdown::Write)?;
+        self.socket.set_read_timeout(Some(timeout))?;
+        match std::io::copy(&mut self.socket, &mut std::io::sink()) {
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) =>
+            {
+                Ok(())
+            }
+            result => result.map(drop),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(tls) = &mut self.tls else {
+            return self.socket.read(buf);
+        };
+
+        loop {
+            match tls.reader().read(buf) {
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+
+            tls.read_tls(&mut self.socket)?;
+            tls.process_new_packets().map_err(std::io::Error::other)?;
+            while tls.wants_write() {
+                tls.write_tls(&mut self.socket)?;
+            }
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let Some(tls) = &mut self.tls else {
+            return self.socket.write(buf);
+        };
+
+        let written = tls.writer().write(buf)?;
+        while tls.wants_write() {
+            tls.write_tls(&mut self.socket)?;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(tls) = &mut self.tls {
+            tls.writer().flush()?;
+            while tls.wants_write() {
+                tls.write_tls(&mut self.socket)?;
+            }
+        }
+
+        self.socket.flush()
+    }
+}
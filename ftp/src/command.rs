@@ -1,15 +1,18 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
 
 // FTP commands according to RFC 959
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum Command {
     // Access control
-    UserName(Vec<u8>),
-    Password(Vec<u8>),
-    Account(Vec<u8>),
-    ChangeWorkingDirectory(Vec<u8>),
+    UserName(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    Password(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    Account(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    ChangeWorkingDirectory(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>,
+    ),
     ChangeToParentDirectory,
-    StructureMount(Vec<u8>),
+    StructureMount(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     Reinitialize,
     Logout,
 
@@ -21,30 +24,75 @@ pub enum Command {
     TransferMode(TransferModeKind),
 
     // FTP service
-    Retrieve(Vec<u8>),
-    Store(Vec<u8>),
+    Retrieve(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    Store(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     StoreUnique,
-    Append(Vec<u8>),
+    Append(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     Allocate(i64, Option<i64>),
-    Restart(Vec<u8>),
-    RenameFrom(Vec<u8>),
-    RenameTo(Vec<u8>),
+    Restart(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = pr_string))] Vec<u8>),
+    RenameFrom(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    RenameTo(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     Abort,
-    Delete(Vec<u8>),
-    RemoveDirectory(Vec<u8>),
-    MakeDirectory(Vec<u8>),
+    Delete(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    RemoveDirectory(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>,
+    ),
+    MakeDirectory(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     PrintWorkingDirectory,
-    List(Option<Vec<u8>>),
-    NameList(Option<Vec<u8>>),
-    SiteParameters(Vec<u8>),
+    List(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
+    NameList(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
+    SiteParameters(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
     System,
-    Status(Option<Vec<u8>>),
-    Help(Option<Vec<u8>>),
+    Status(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
+    Help(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
     #[default]
     Noop,
+
+    // Security extensions according to RFC 2228
+    Authentication(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    ProtectionBufferSize(u32),
+    DataChannelProtection(ProtectionLevel),
+    ClearCommandChannel,
+
+    // Feature negotiation according to RFC 2389
+    Features,
+    Options(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = pr_string))] Vec<u8>,
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
+
+    // Extensions for IPv6 and NATs according to RFC 2428
+    ExtendedDataPort(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = socket_address))] SocketAddr,
+    ),
+    ExtendedPassive(Option<ExtendedPassiveKind>),
+
+    // Extensions according to RFC 3659
+    FileSize(#[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>),
+    ModificationTime(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = string))] Vec<u8>,
+    ),
+    MachineListDirectory(
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = optional_string))]
+        Option<Vec<u8>>,
+    ),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum FormatControl {
     NonPrint,
     Telnet,
@@ -52,6 +100,7 @@ pub enum FormatControl {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum RepresentationTypeKind {
     Ascii(Option<FormatControl>),
     Ebcdic(Option<FormatControl>),
@@ -60,6 +109,7 @@ pub enum RepresentationTypeKind {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum FileStructureKind {
     File,
     Record,
@@ -67,8 +117,78 @@ pub enum FileStructureKind {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum TransferModeKind {
     Stream,
     Block,
     Compressed,
+    /// Deflate compression, which isn't part of RFC 959 but widely supported.
+    Deflate,
+}
+
+/// The protection of data connections set by a `PROT` command.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+pub enum ProtectionLevel {
+    Clear,
+    Safe,
+    Confidential,
+    Private,
+}
+
+/// The argument of an `EPSV` command.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+pub enum ExtendedPassiveKind {
+    /// The network protocol to listen on, 1 for IPv4 and 2 for IPv6.
+    Protocol(u8),
+    /// Tells the server that only `EPSV` will set up data connections from now on.
+    All,
+}
+
+// <string> ::= <char> | <char><string>
+// <char> ::= any of the 128 ASCII characters except <CR> and <LF>
+#[cfg(any(test, feature = "arbitrary"))]
+fn string(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Vec<u8>> {
+    let mut string = vec![u.int_in_range(0..=127u8)?];
+    for _ in 0..u.arbitrary_len::<u8>()? {
+        string.push(u.int_in_range(0..=127u8)?);
+    }
+
+    for c in &mut string {
+        if *c == b'\r' || *c == b'\n' {
+            *c = b' ';
+        }
+    }
+
+    Ok(string)
+}
+
+#[cfg(any(test, feature = "arbitrary"))]
+fn optional_string(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Option<Vec<u8>>> {
+    Ok(if u.arbitrary()? {
+        Some(string(u)?)
+    } else {
+        None
+    })
+}
+
+// <pr-string> ::= <pr-char> | <pr-char><pr-string>
+// <pr-char> ::= printable characters, any ASCII code 33 through 126
+#[cfg(any(test, feature = "arbitrary"))]
+fn pr_string(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Vec<u8>> {
+    let mut string = vec![u.int_in_range(33..=126u8)?];
+    for _ in 0..u.arbitrary_len::<u8>()? {
+        string.push(u.int_in_range(33..=126u8)?);
+    }
+
+    Ok(string)
+}
+
+// Ports and addresses an `EPRT` command can carry,
+// which have no flow information or scope id.
+#[cfg(any(test, feature = "arbitrary"))]
+fn socket_address(u: &mut arbitrary::Unstructured) -> arbitrary::Result<SocketAddr> {
+    let ip: std::net::IpAddr = u.arbitrary()?;
+    Ok(SocketAddr::new(ip, u.arbitrary()?))
 }
@@ -1,4 +1,9 @@
 pub mod command;
+pub mod listing;
 pub mod parser;
 pub mod reply;
 pub mod serializer;
+pub mod transfer;
+
+#[cfg(test)]
+mod round_trip;
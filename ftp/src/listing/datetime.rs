@@ -0,0 +1,199 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A calendar date and time in UTC as found in directory listings.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    pub fn new(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 61
+    }
+
+    /// Seconds since the Unix epoch, negative for earlier dates.
+    pub fn timestamp(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * 86400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second)
+    }
+
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(86400);
+        let seconds = timestamp.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    /// Parses a `<time-val>` of RFC 3659 such as `20200101120000.5`,
+    /// ignoring fractions of a second.
+    pub fn from_time_val(value: &[u8]) -> Option<Self> {
+        let (seconds, fraction) = match value.iter().position(|&c| c == b'.') {
+            Some(dot) => (&value[..dot], &value[dot + 1..]),
+            None => (value, &b"0"[..]),
+        };
+        if seconds.len() != 14
+            || fraction.is_empty()
+            || !seconds.iter().chain(fraction).all(u8::is_ascii_digit)
+        {
+            return None;
+        }
+
+        fn number<T: std::str::FromStr>(digits: &[u8]) -> Option<T> {
+            std::str::from_utf8(digits).ok()?.parse().ok()
+        }
+
+        let time = Self {
+            year: number(&seconds[0..4])?,
+            month: number(&seconds[4..6])?,
+            day: number(&seconds[6..8])?,
+            hour: number(&seconds[8..10])?,
+            minute: number(&seconds[10..12])?,
+            second: number(&seconds[12..14])?,
+        };
+
+        time.is_valid().then_some(time)
+    }
+
+    /// Formats a `<time-val>` of RFC 3659 such as `20200101120000`.
+    pub fn time_val(&self) -> Vec<u8> {
+        format!(
+            "{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+        .into_bytes()
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        let timestamp = self.timestamp();
+        if timestamp >= 0 {
+            UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+        }
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let timestamp = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(error) => -(error.duration().as_secs() as i64),
+        };
+
+        Self::from_timestamp(timestamp)
+    }
+}
+
+pub(crate) fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+pub(crate) const MONTHS: [&[u8; 3]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// Parses an english three letter month abbreviation, ignoring case.
+pub(crate) fn month_from_abbreviation(name: &[u8]) -> Option<u8> {
+    MONTHS
+        .iter()
+        .position(|month| month.eq_ignore_ascii_case(name))
+        .map(|index| index as u8 + 1)
+}
+
+// Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i32, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year as i32, month as u8, day as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DateTime;
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        for timestamp in [-86401, 0, 951_782_400, 1_700_000_000, 4_102_444_800] {
+            assert_eq!(DateTime::from_timestamp(timestamp).timestamp(), timestamp);
+        }
+    }
+
+    #[test]
+    fn test_time_val() {
+        let time = DateTime::new(2020, 1, 1, 12, 0, 5);
+        assert_eq!(DateTime::from_time_val(b"20200101120005"), Some(time));
+        assert_eq!(DateTime::from_time_val(b"20200101120005.123"), Some(time));
+        assert_eq!(time.time_val(), b"20200101120005");
+
+        assert_eq!(DateTime::from_time_val(b"20201301120005"), None);
+        assert_eq!(DateTime::from_time_val(b"20200101120005."), None);
+        assert_eq!(DateTime::from_time_val(b"2020010112"), None);
+    }
+
+    #[test]
+    fn test_leap_day() {
+        assert_eq!(
+            DateTime::from_timestamp(951_782_400),
+            DateTime::new(2000, 2, 29, 0, 0, 0)
+        );
+    }
+}
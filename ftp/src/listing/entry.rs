@@ -0,0 +1,108 @@
+use std::{ffi::OsStr, fs::Metadata, path::Path};
+
+use super::DateTime;
+
+/// A single entry of a directory listing, independent of the format
+/// the server listed it in. Fields the format doesn't carry are `None`.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct ListEntry {
+    pub name: Vec<u8>,
+    pub kind: EntryKind,
+    pub size: Option<u64>,
+    pub modified: Option<DateTime>,
+    /// Unix permission bits, including the setuid, setgid and sticky bits.
+    pub permissions: Option<u32>,
+    pub link_count: Option<u64>,
+    pub owner: Option<Vec<u8>>,
+    pub group: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub enum EntryKind {
+    #[default]
+    File,
+    Directory,
+    /// The directory listed itself, which `MLSD` lists as `type=cdir`,
+    /// under any name.
+    CurrentDirectory,
+    /// The parent of the directory listed, `type=pdir` in `MLSD`.
+    ParentDirectory,
+    Symlink {
+        target: Vec<u8>,
+    },
+    /// Devices, pipes, sockets and anything else which isn't
+    /// a regular file, directory or symbolic link.
+    Other,
+}
+
+impl EntryKind {
+    /// Whether the entry is a directory, including the one listed and its parent.
+    pub fn is_directory(&self) -> bool {
+        matches!(
+            self,
+            Self::Directory | Self::CurrentDirectory | Self::ParentDirectory
+        )
+    }
+}
+
+impl ListEntry {
+    /// Lists the file at `path` without following it if it's a symbolic link.
+    pub fn from_path(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        let mut entry =
+            Self::from_metadata(path_bytes(path.file_name().unwrap_or_default()), &metadata);
+        if let EntryKind::Symlink { target } = &mut entry.kind {
+            *target = path_bytes(std::fs::read_link(path)?.as_os_str());
+        }
+
+        Ok(entry)
+    }
+
+    /// Lists a file named `name` from its metadata. Symbolic links
+    /// are listed without a target, which `from_path` fills in.
+    pub fn from_metadata(name: Vec<u8>, metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() {
+            EntryKind::Directory
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink { target: Vec::new() }
+        } else {
+            EntryKind::Other
+        };
+
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut entry = ListEntry {
+            name,
+            kind,
+            size: Some(metadata.len()),
+            modified: metadata.modified().ok().map(DateTime::from),
+            ..Default::default()
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            entry.permissions = Some(metadata.mode() & 0o7777);
+            entry.link_count = Some(metadata.nlink());
+            entry.owner = Some(metadata.uid().to_string().into_bytes());
+            entry.group = Some(metadata.gid().to_string().into_bytes());
+        }
+
+        entry
+    }
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &OsStr) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
@@ -0,0 +1,66 @@
+use super::{lines, number, DateTime, EntryKind, ListEntry};
+
+/// Parses the output of a `LIST` command in the Easily Parsed LIST Format
+/// described at <https://cr.yp.to/ftp/list/eplf.html>.
+pub fn parse_eplf_listing(listing: &[u8]) -> Vec<ListEntry> {
+    lines(listing).filter_map(parse_eplf_line).collect()
+}
+
+/// Parses a single EPLF line such as `+i8388621.29609,m824255902,/,\tname`.
+pub fn parse_eplf_line(line: &[u8]) -> Option<ListEntry> {
+    let line = line.strip_prefix(b"+")?;
+    let tab = line.iter().position(|&c| c == b'\t')?;
+    let (facts, name) = (&line[..tab], &line[tab + 1..]);
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut entry = ListEntry {
+        name: name.to_vec(),
+        ..Default::default()
+    };
+
+    // Unknown facts must be ignored.
+    for fact in facts.split(|&c| c == b',') {
+        match fact {
+            [b'/'] => entry.kind = EntryKind::Directory,
+            [b'r'] => entry.kind = EntryKind::File,
+            [b's', size @ ..] => entry.size = number(size),
+            [b'm', time @ ..] => entry.modified = number(time).map(DateTime::from_timestamp),
+            [b'u', b'p', permissions @ ..] => {
+                entry.permissions = std::str::from_utf8(permissions)
+                    .ok()
+                    .and_then(|permissions| u32::from_str_radix(permissions, 8).ok())
+            }
+            _ => {}
+        }
+    }
+
+    Some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{DateTime, EntryKind};
+
+    use super::parse_eplf_line;
+
+    #[test]
+    fn test_file() {
+        let entry = parse_eplf_line(b"+i8388621.48594,m825718503,r,s280,up644,\tdjb.html").unwrap();
+
+        assert_eq!(entry.name, b"djb.html");
+        assert_eq!(entry.kind, EntryKind::File);
+        assert_eq!(entry.size, Some(280));
+        assert_eq!(entry.permissions, Some(0o644));
+        assert_eq!(entry.modified, Some(DateTime::new(1996, 3, 1, 22, 15, 3)));
+    }
+
+    #[test]
+    fn test_directory() {
+        let entry = parse_eplf_line(b"+i8388621.50690,m824255907,/,\t514").unwrap();
+
+        assert_eq!(entry.name, b"514");
+        assert_eq!(entry.kind, EntryKind::Directory);
+    }
+}
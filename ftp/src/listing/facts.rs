@@ -0,0 +1,90 @@
+/// Facts about a file listed by the `MLST` and `MLSD` commands
+/// according to RFC 3659 section 7.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Fact {
+    Type,
+    Size,
+    Modify,
+    Perm,
+    UnixMode,
+    UnixOwner,
+    UnixGroup,
+}
+
+impl Fact {
+    /// Every supported fact, in the order they are listed.
+    pub const ALL: [Fact; 7] = [
+        Fact::Type,
+        Fact::Size,
+        Fact::Modify,
+        Fact::Perm,
+        Fact::UnixMode,
+        Fact::UnixOwner,
+        Fact::UnixGroup,
+    ];
+
+    pub fn name(&self) -> &'static [u8] {
+        match self {
+            Fact::Type => b"type",
+            Fact::Size => b"size",
+            Fact::Modify => b"modify",
+            Fact::Perm => b"perm",
+            Fact::UnixMode => b"UNIX.mode",
+            Fact::UnixOwner => b"UNIX.owner",
+            Fact::UnixGroup => b"UNIX.group",
+        }
+    }
+
+    /// Fact names are case insensitive.
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|fact| fact.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Parses the fact list of an `OPTS MLST type;size;modify;` command.
+    /// Unsupported facts are ignored, as the RFC requires.
+    pub fn parse_selection(list: &[u8]) -> Vec<Self> {
+        let mut selection: Vec<_> = list
+            .split(|&c| c == b';')
+            .filter_map(Self::from_name)
+            .collect();
+        selection.sort();
+        selection.dedup();
+        selection
+    }
+
+    /// Formats the `MLST` line of a `FEAT` reply, marking the selected
+    /// facts with an asterisk, e.g. "MLST type*;size*;modify;".
+    pub fn feature_line(selection: &[Self]) -> Vec<u8> {
+        let mut line = b"MLST ".to_vec();
+        for fact in Self::ALL {
+            line.extend_from_slice(fact.name());
+            if selection.contains(&fact) {
+                line.push(b'*');
+            }
+
+            line.push(b';');
+        }
+
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fact;
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(
+            Fact::parse_selection(b"Size;type;unknown;unix.MODE;"),
+            [Fact::Type, Fact::Size, Fact::UnixMode]
+        );
+    }
+
+    #[test]
+    fn test_empty_selection() {
+        assert_eq!(Fact::parse_selection(b""), []);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::reply::{Reply, Text};
+
+use super::{
+    lines, parse_eplf_line, parse_eplf_listing, parse_unix_line, parse_unix_listing,
+    parse_vms_line, parse_vms_listing, parse_windows_line, parse_windows_listing, DateTime,
+    ListEntry,
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ListingFormat {
+    Unix,
+    Windows,
+    Eplf,
+    Vms,
+}
+
+impl ListingFormat {
+    const ALL: [ListingFormat; 4] = [
+        ListingFormat::Unix,
+        ListingFormat::Windows,
+        ListingFormat::Eplf,
+        ListingFormat::Vms,
+    ];
+
+    /// Guesses the listing format from the reply to a `SYST` command,
+    /// e.g. "215 UNIX Type: L8" or "215 Windows_NT".
+    pub fn from_system_reply(reply: &Reply) -> Option<Self> {
+        let line = match &reply.text {
+            Text::SingleLine { line } => line,
+            Text::MultiLine { lines, .. } => lines.first()?,
+        };
+        let system = line.split(|&c| c == b' ').next()?.to_ascii_uppercase();
+
+        match &system[..] {
+            b"UNIX" => Some(ListingFormat::Unix),
+            b"WINDOWS_NT" | b"WINDOWS" => Some(ListingFormat::Windows),
+            b"VMS" | b"OPENVMS" => Some(ListingFormat::Vms),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of a single listing line.
+    pub fn detect(line: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.matches(line))
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        // Unix listings need a reference date for their year
+        // inference, which doesn't affect whether a line matches.
+        match self {
+            ListingFormat::Unix => parse_unix_line(line, DateTime::default()).is_some(),
+            ListingFormat::Windows => parse_windows_line(line).is_some(),
+            ListingFormat::Eplf => parse_eplf_line(line).is_some(),
+            ListingFormat::Vms => parse_vms_line(line).is_some(),
+        }
+    }
+
+    pub fn parse(&self, listing: &[u8], now: DateTime) -> Vec<ListEntry> {
+        match self {
+            ListingFormat::Unix => parse_unix_listing(listing, now),
+            ListingFormat::Windows => parse_windows_listing(listing),
+            ListingFormat::Eplf => parse_eplf_listing(listing),
+            ListingFormat::Vms => parse_vms_listing(listing),
+        }
+    }
+}
+
+/// Parses the output of a `LIST` command in any of the supported formats.
+///
+/// The reply to `SYST`, when given, is used as a hint for the format and
+/// is preferred as long as the listing contains a line in that format,
+/// since servers often emulate another system's listing format.
+/// Otherwise the format of the first recognized line is used.
+pub fn parse_listing(listing: &[u8], system: Option<&Reply>, now: DateTime) -> Vec<ListEntry> {
+    let hint = system.and_then(ListingFormat::from_system_reply);
+    let format = hint
+        .filter(|hint| lines(listing).any(|line| hint.matches(line)))
+        .or_else(|| lines(listing).find_map(ListingFormat::detect));
+
+    match format {
+        Some(format) => format.parse(listing, now),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        listing::{DateTime, EntryKind},
+        reply::Reply,
+    };
+
+    use super::{parse_listing, ListingFormat};
+
+    #[test]
+    fn test_from_system_reply() {
+        assert_eq!(
+            ListingFormat::from_system_reply(&Reply::system_type("UNIX Type: L8")),
+            Some(ListingFormat::Unix)
+        );
+        assert_eq!(
+            ListingFormat::from_system_reply(&Reply::system_type("Windows_NT")),
+            Some(ListingFormat::Windows)
+        );
+        assert_eq!(
+            ListingFormat::from_system_reply(&Reply::system_type("MACOS Peter's Server")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hint_falls_back_to_detection() {
+        // IIS configured to emulate Unix listings still answers Windows_NT.
+        let listing = b"-rw-r--r-- 1 owner group 42 Jan  1  2020 file.txt\r\n";
+        let entries = parse_listing(
+            listing,
+            Some(&Reply::system_type("Windows_NT")),
+            DateTime::default(),
+        );
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].size, Some(42));
+    }
+
+    #[test]
+    fn test_detection_without_hint() {
+        let listing = b"01-15-20  03:45PM       <DIR>          folder\r\n";
+        let entries = parse_listing(listing, None, DateTime::default());
+
+        assert_eq!(entries[0].kind, EntryKind::Directory);
+    }
+}
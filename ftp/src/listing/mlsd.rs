@@ -0,0 +1,115 @@
+use super::{number, DateTime, EntryKind, Fact, ListEntry};
+
+/// Parses the output of an `MLSD` command according to RFC 3659 section 7.
+pub fn parse_mlsd_listing(listing: &[u8]) -> Vec<ListEntry> {
+    super::lines(listing).filter_map(parse_mlsd_line).collect()
+}
+
+/// Parses a single fact line such as `type=file;size=42;modify=20200101120000; name`.
+pub fn parse_mlsd_line(line: &[u8]) -> Option<ListEntry> {
+    let space = line.iter().position(|&c| c == b' ')?;
+    let (facts, name) = (&line[..space], &line[space + 1..]);
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut entry = ListEntry {
+        name: name.to_vec(),
+        ..Default::default()
+    };
+
+    // Unknown facts must be ignored.
+    for fact in facts.split(|&c| c == b';') {
+        let Some(equals) = fact.iter().position(|&c| c == b'=') else {
+            continue;
+        };
+
+        let value = &fact[equals + 1..];
+        match Fact::from_name(&fact[..equals]) {
+            Some(Fact::Type) => entry.kind = entry_kind(value),
+            Some(Fact::Size) => entry.size = number(value),
+            Some(Fact::Modify) => entry.modified = DateTime::from_time_val(value),
+            Some(Fact::UnixMode) => {
+                entry.permissions = std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+            }
+            Some(Fact::UnixOwner) => entry.owner = Some(value.to_vec()),
+            Some(Fact::UnixGroup) => entry.group = Some(value.to_vec()),
+            Some(Fact::Perm) | None => {}
+        }
+    }
+
+    Some(entry)
+}
+
+// The value of the type fact, whose names are case insensitive.
+fn entry_kind(value: &[u8]) -> EntryKind {
+    let lowercase = value.to_ascii_lowercase();
+    match &lowercase[..] {
+        b"file" => EntryKind::File,
+        b"dir" => EntryKind::Directory,
+        b"cdir" => EntryKind::CurrentDirectory,
+        b"pdir" => EntryKind::ParentDirectory,
+        _ => match lowercase.strip_prefix(b"os.unix=slink:") {
+            Some(_) => EntryKind::Symlink {
+                target: value[b"os.unix=slink:".len()..].to_vec(),
+            },
+            None => EntryKind::Other,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{format_mlsd_line, DateTime, EntryKind, Fact, ListEntry};
+
+    use super::parse_mlsd_line;
+
+    #[test]
+    fn test_file() {
+        let entry =
+            parse_mlsd_line(b"Type=file;Size=280;Modify=19960301221503.5;UNIX.mode=0644; a b.html")
+                .unwrap();
+
+        assert_eq!(entry.name, b"a b.html");
+        assert_eq!(entry.kind, EntryKind::File);
+        assert_eq!(entry.size, Some(280));
+        assert_eq!(entry.permissions, Some(0o644));
+        assert_eq!(entry.modified, Some(DateTime::new(1996, 3, 1, 22, 15, 3)));
+    }
+
+    #[test]
+    fn test_kinds() {
+        let kind = |line: &[u8]| parse_mlsd_line(line).unwrap().kind;
+
+        assert_eq!(kind(b"type=cdir; ."), EntryKind::CurrentDirectory);
+        assert_eq!(kind(b"type=PDir; /pub"), EntryKind::ParentDirectory);
+        assert_eq!(kind(b"type=dir;unknown=1; docs"), EntryKind::Directory);
+        assert_eq!(
+            kind(b"type=OS.unix=slink:/etc; etc"),
+            EntryKind::Symlink {
+                target: b"/etc".to_vec()
+            }
+        );
+        assert_eq!(kind(b"type=OS.unix=chr-13/29; tty"), EntryKind::Other);
+        assert!(parse_mlsd_line(b"type=file;").is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let entry = ListEntry {
+            name: b"notes.txt".to_vec(),
+            kind: EntryKind::File,
+            size: Some(42),
+            modified: Some(DateTime::new(2020, 1, 1, 12, 0, 0)),
+            permissions: Some(0o640),
+            owner: Some(b"1000".to_vec()),
+            group: Some(b"100".to_vec()),
+            ..Default::default()
+        };
+
+        let line = format_mlsd_line(&entry, &Fact::ALL);
+        assert_eq!(parse_mlsd_line(&line), Some(entry));
+    }
+}
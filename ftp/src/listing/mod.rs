@@ -0,0 +1,54 @@
+mod datetime;
+mod entry;
+mod eplf;
+mod facts;
+mod format;
+mod mlsd;
+mod unix;
+mod vms;
+mod windows;
+mod writer;
+
+use std::str::FromStr;
+
+use nom::{
+    bytes::complete::take_while_m_n,
+    combinator::{all_consuming, map_res},
+    IResult,
+};
+
+pub use datetime::DateTime;
+pub use entry::{EntryKind, ListEntry};
+pub use eplf::{parse_eplf_line, parse_eplf_listing};
+pub use facts::Fact;
+pub use format::{parse_listing, ListingFormat};
+pub use mlsd::{parse_mlsd_line, parse_mlsd_listing};
+pub use unix::{parse_unix_line, parse_unix_listing};
+pub use vms::{parse_vms_line, parse_vms_listing};
+pub use windows::{parse_windows_line, parse_windows_listing};
+pub use writer::{
+    format_mlsd_line, format_unix_line, write_mlsd_listing, write_name_list, write_unix_listing,
+};
+
+/// Splits a listing into its lines, accepting both CRLF and bare LF
+/// line endings and skipping empty lines.
+fn lines(listing: &[u8]) -> impl Iterator<Item = &[u8]> {
+    listing
+        .split(|&c| c == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+}
+
+// A decimal number of `min` to `max` digits.
+fn digits<'a, T: FromStr>(min: usize, max: usize) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], T> {
+    map_res(
+        take_while_m_n(min, max, |c: u8| c.is_ascii_digit()),
+        |digits: &[u8]| String::from_utf8_lossy(digits).parse(),
+    )
+}
+
+// A whole slice of decimal digits.
+fn number<T: FromStr>(i: &[u8]) -> Option<T> {
+    let result: IResult<_, _> = all_consuming(digits(1, 20))(i);
+    result.ok().map(|(_, number)| number)
+}
@@ -0,0 +1,314 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{take, take_while1},
+    character::complete::{char, one_of, space1},
+    combinator::{map, map_opt, opt, verify},
+    sequence::{preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+
+use super::{
+    datetime::month_from_abbreviation, digits, lines, number, DateTime, EntryKind, ListEntry,
+};
+
+/// Parses the output of a `LIST` command formatted like `ls -l`.
+/// Lines which aren't entries, such as the leading "total" line, are skipped.
+pub fn parse_unix_listing(listing: &[u8], now: DateTime) -> Vec<ListEntry> {
+    lines(listing)
+        .filter_map(|line| parse_unix_line(line, now))
+        .collect()
+}
+
+/// Parses a single `ls -l` line such as
+/// `drwxr-xr-x 2 owner group 4096 Jan  1 12:34 name`.
+///
+/// Dates without a year, which `ls` uses for recently modified files,
+/// are placed in the year preceding `now` in which they aren't in the future.
+pub fn parse_unix_line(line: &[u8], now: DateTime) -> Option<ListEntry> {
+    let (i, (kind, permissions)) = terminated(permissions, opt(one_of("+.@")))(line).ok()?;
+    let (i, link_count) = preceded(space1, digits(1, 20))(i).ok()?;
+    let (i, _) = space1::<_, nom::error::Error<_>>(i).ok()?;
+
+    // The owner, group and size columns are told apart by the date that
+    // follows them, as some servers omit the group and devices list
+    // their major and minor numbers instead of a size.
+    let mut columns = Vec::new();
+    let mut i = i;
+    let (date, name) = loop {
+        if let Ok((name, date)) = terminated(date, char(' '))(i) {
+            if !name.is_empty() {
+                break (date, name);
+            }
+        }
+
+        let (remaining, column) = terminated(
+            take_while1(|c: u8| c != b' '),
+            space1::<_, nom::error::Error<_>>,
+        )(i)
+        .ok()?;
+        columns.push(column);
+        i = remaining;
+    };
+
+    let (owner, group, size) = match (kind == b'c' || kind == b'b', &columns[..]) {
+        (false, [owner, group, size]) => (Some(*owner), Some(*group), Some(number(size)?)),
+        (false, [owner, size]) => (Some(*owner), None, Some(number(size)?)),
+        (true, [owner, group, _, _]) => (Some(*owner), Some(*group), None),
+        (true, [owner, _, _]) => (Some(*owner), None, None),
+        _ => return None,
+    };
+
+    let (name, kind) = match kind {
+        b'-' => (name, EntryKind::File),
+        b'd' => (name, EntryKind::Directory),
+        b'l' => match find(name, b" -> ") {
+            Some(index) => (
+                &name[..index],
+                EntryKind::Symlink {
+                    target: name[index + 4..].to_vec(),
+                },
+            ),
+            None => (name, EntryKind::Symlink { target: Vec::new() }),
+        },
+        _ => (name, EntryKind::Other),
+    };
+
+    Some(ListEntry {
+        name: name.to_vec(),
+        kind,
+        size,
+        modified: date.resolve(now),
+        permissions: Some(permissions),
+        link_count: Some(link_count),
+        owner: owner.map(<[u8]>::to_vec),
+        group: group.map(<[u8]>::to_vec),
+    })
+}
+
+enum ListedDate {
+    // "Mon DD HH:MM"
+    Recent {
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+    },
+    // "Mon DD YYYY" or "YYYY-MM-DD HH:MM"
+    Full(DateTime),
+}
+
+impl ListedDate {
+    fn resolve(self, now: DateTime) -> Option<DateTime> {
+        let date = match self {
+            ListedDate::Recent {
+                month,
+                day,
+                hour,
+                minute,
+            } => {
+                // A day of tolerance for clock skew and time zones
+                // between the server and `now`.
+                let mut date = DateTime::new(now.year, month, day, hour, minute, 0);
+                if date.timestamp() > now.timestamp() + 86400 {
+                    date.year -= 1;
+                }
+
+                // February 29th goes back to the last leap year.
+                while month == 2 && day == 29 && !date.is_valid() && date.year > now.year - 8 {
+                    date.year -= 1;
+                }
+
+                date
+            }
+            ListedDate::Full(date) => date,
+        };
+
+        date.is_valid().then_some(date)
+    }
+}
+
+// <type> <permissions>, e.g. "drwxr-xr-x"
+fn permissions(i: &[u8]) -> IResult<&[u8], (u8, u32)> {
+    let (i, kind) = one_of("-dlcbpsD")(i)?;
+    let (i, bits) = take(9usize)(i)?;
+
+    let mut mode = 0;
+    for (index, &c) in bits.iter().enumerate() {
+        let bit = 1 << (8 - index);
+        let special = match index {
+            2 => 0o4000,
+            5 => 0o2000,
+            8 => 0o1000,
+            _ => 0,
+        };
+
+        mode |= match (index % 3, c) {
+            (_, b'-') => 0,
+            (0, b'r') | (1, b'w') | (2, b'x') => bit,
+            (2, b's') | (2, b't') => bit | special,
+            (2, b'S') | (2, b'T') => special,
+            _ => {
+                return Err(nom::Err::Error(nom::error::Error::new(
+                    i,
+                    nom::error::ErrorKind::OneOf,
+                )))
+            }
+        };
+    }
+
+    Ok((i, (kind as u8, mode)))
+}
+
+fn date(i: &[u8]) -> IResult<&[u8], ListedDate> {
+    alt((
+        // Mon DD HH:MM
+        map(
+            tuple((month, preceded(space1, day), preceded(space1, time))),
+            |(month, day, (hour, minute))| ListedDate::Recent {
+                month,
+                day,
+                hour,
+                minute,
+            },
+        ),
+        // Mon DD YYYY
+        map(
+            tuple((month, preceded(space1, day), preceded(space1, year))),
+            |(month, day, year)| ListedDate::Full(DateTime::new(year, month, day, 0, 0, 0)),
+        ),
+        // YYYY-MM-DD HH:MM, as printed by `ls --time-style=long-iso`
+        map(
+            tuple((
+                year,
+                preceded(char('-'), digits(2, 2)),
+                preceded(char('-'), digits(2, 2)),
+                preceded(space1, time),
+            )),
+            |(year, month, day, (hour, minute))| {
+                ListedDate::Full(DateTime::new(year, month, day, hour, minute, 0))
+            },
+        ),
+    ))(i)
+}
+
+fn month(i: &[u8]) -> IResult<&[u8], u8> {
+    map_opt(take(3usize), month_from_abbreviation)(i)
+}
+
+fn day(i: &[u8]) -> IResult<&[u8], u8> {
+    verify(digits(1, 2), |day| (1..=31).contains(day))(i)
+}
+
+fn time(i: &[u8]) -> IResult<&[u8], (u8, u8)> {
+    separated_pair(digits(1, 2), char(':'), digits(2, 2))(i)
+}
+
+fn year(i: &[u8]) -> IResult<&[u8], i32> {
+    digits(4, 4)(i)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{DateTime, EntryKind, ListEntry};
+
+    use super::{parse_unix_line, parse_unix_listing};
+
+    const NOW: DateTime = DateTime {
+        year: 2023,
+        month: 3,
+        day: 15,
+        hour: 12,
+        minute: 0,
+        second: 0,
+    };
+
+    #[test]
+    fn test_directory_with_time() {
+        assert_eq!(
+            parse_unix_line(b"drwxr-xr-x   2 user group     4096 Mar  1 09:30 docs", NOW),
+            Some(ListEntry {
+                name: b"docs".to_vec(),
+                kind: EntryKind::Directory,
+                size: Some(4096),
+                modified: Some(DateTime::new(2023, 3, 1, 9, 30, 0)),
+                permissions: Some(0o755),
+                link_count: Some(2),
+                owner: Some(b"user".to_vec()),
+                group: Some(b"group".to_vec()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_file_with_year_and_spaces_in_name() {
+        let entry = parse_unix_line(
+            b"-rw-r--r--   1 user group  1234567 Jan  1  2020 a  b.txt",
+            NOW,
+        )
+        .unwrap();
+
+        assert_eq!(entry.name, b"a  b.txt");
+        assert_eq!(entry.kind, EntryKind::File);
+        assert_eq!(entry.size, Some(1234567));
+        assert_eq!(entry.modified, Some(DateTime::new(2020, 1, 1, 0, 0, 0)));
+        assert_eq!(entry.permissions, Some(0o644));
+    }
+
+    #[test]
+    fn test_time_after_now_is_previous_year() {
+        let entry = parse_unix_line(b"-rw-r--r-- 1 user group 0 Dec 24 18:00 gift", NOW).unwrap();
+
+        assert_eq!(entry.modified, Some(DateTime::new(2022, 12, 24, 18, 0, 0)));
+    }
+
+    #[test]
+    fn test_symlink_without_group() {
+        let entry =
+            parse_unix_line(b"lrwxrwxrwx 1 user 7 Mar 10 08:00 latest -> v1.2.3", NOW).unwrap();
+
+        assert_eq!(entry.name, b"latest");
+        assert_eq!(
+            entry.kind,
+            EntryKind::Symlink {
+                target: b"v1.2.3".to_vec()
+            }
+        );
+        assert_eq!(entry.owner, Some(b"user".to_vec()));
+        assert_eq!(entry.group, None);
+    }
+
+    #[test]
+    fn test_special_permission_bits() {
+        let entry =
+            parse_unix_line(b"drwxrwsr-t+ 3 root staff 512 Mar 10 08:00 shared", NOW).unwrap();
+
+        assert_eq!(entry.permissions, Some(0o3775));
+    }
+
+    #[test]
+    fn test_device() {
+        let entry = parse_unix_line(b"crw-rw-rw- 1 root root 1, 3 Mar 10 08:00 null", NOW).unwrap();
+
+        assert_eq!(entry.kind, EntryKind::Other);
+        assert_eq!(entry.size, None);
+    }
+
+    #[test]
+    fn test_listing_skips_total() {
+        let listing = b"total 8\r\n\
+            -rw-r--r-- 1 user group 1 Mar 10 08:00 a\r\n\
+            -rw-r--r-- 1 user group 2 Mar 10 08:00 b\r\n";
+
+        let names: Vec<_> = parse_unix_listing(listing, NOW)
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, [b"a".to_vec(), b"b".to_vec()]);
+    }
+}
@@ -0,0 +1,199 @@
+use nom::{
+    bytes::complete::{take, take_till1, take_while, take_while1},
+    character::complete::{char, space0, space1},
+    combinator::{all_consuming, map_opt, opt},
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+use super::{datetime::month_from_abbreviation, digits, lines, DateTime, EntryKind, ListEntry};
+
+// Size of a disk block, the unit VMS lists file sizes in.
+const BLOCK_SIZE: u64 = 512;
+
+/// Parses the output of a `LIST` command sent by an OpenVMS server.
+/// Headers, totals and error lines are skipped, and names too long to
+/// share a line with their attributes are joined with the next line.
+pub fn parse_vms_listing(listing: &[u8]) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    let mut lines = lines(listing).peekable();
+    while let Some(line) = lines.next() {
+        if let Some(entry) = parse_vms_line(line) {
+            entries.push(entry);
+            continue;
+        }
+
+        let is_wrapped_name = !line.contains(&b' ') && line.contains(&b';');
+        if let (true, Some(next)) = (is_wrapped_name, lines.peek()) {
+            let joined = [line, b" ", next].concat();
+            if let Some(entry) = parse_vms_line(&joined) {
+                entries.push(entry);
+                lines.next();
+            }
+        }
+    }
+
+    entries
+}
+
+/// Parses a single VMS line such as
+/// `NAME.EXT;3  12/18  1-JAN-2020 10:00 [GROUP,OWNER] (RWED,RWED,RE,)`.
+pub fn parse_vms_line(line: &[u8]) -> Option<ListEntry> {
+    let (_, (name, used_blocks, modified, owner, permissions)) = all_consuming(tuple((
+        file_name,
+        preceded(space1, blocks),
+        preceded(space1, date_time),
+        opt(preceded(space1, owner)),
+        terminated(opt(preceded(space1, protection)), space0),
+    )))(line)
+    .ok()?;
+
+    let (name, kind) = match name.strip_suffix(b".DIR") {
+        Some(name) => (name, EntryKind::Directory),
+        None => (name, EntryKind::File),
+    };
+    let (group, owner) = match owner {
+        Some((Some(group), owner)) => (Some(group.to_vec()), Some(owner.to_vec())),
+        Some((None, owner)) => (None, Some(owner.to_vec())),
+        None => (None, None),
+    };
+
+    Some(ListEntry {
+        name: name.to_vec(),
+        kind,
+        size: used_blocks.checked_mul(BLOCK_SIZE),
+        modified: modified.is_valid().then_some(modified),
+        permissions,
+        link_count: None,
+        owner,
+        group,
+    })
+}
+
+// NAME.EXT;VERSION, the version is dropped.
+fn file_name(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(
+        take_till1(|c| c == b';' || c == b' '),
+        preceded(char(';'), digits::<u32>(1, 5)),
+    )(i)
+}
+
+// USED[/ALLOCATED]
+fn blocks(i: &[u8]) -> IResult<&[u8], u64> {
+    terminated(
+        digits(1, 20),
+        opt(preceded(char('/'), digits::<u64>(1, 20))),
+    )(i)
+}
+
+// DD-MON-YYYY HH:MM[:SS[.CC]]
+fn date_time(i: &[u8]) -> IResult<&[u8], DateTime> {
+    let (i, (day, month, year)) = tuple((
+        terminated(digits(1, 2), char('-')),
+        terminated(map_opt(take(3usize), month_from_abbreviation), char('-')),
+        digits(4, 4),
+    ))(i)?;
+    let (i, (hour, minute, second)) = preceded(
+        space1,
+        tuple((
+            terminated(digits(1, 2), char(':')),
+            digits(2, 2),
+            opt(preceded(char(':'), digits(2, 2))),
+        )),
+    )(i)?;
+    let (i, _) = opt(preceded(char('.'), digits::<u8>(1, 2)))(i)?;
+
+    Ok((
+        i,
+        DateTime::new(year, month, day, hour, minute, second.unwrap_or(0)),
+    ))
+}
+
+// The group, when listed, and the owner of a file.
+type Owner<'a> = (Option<&'a [u8]>, &'a [u8]);
+
+// [GROUP,OWNER] or [OWNER]
+fn owner(i: &[u8]) -> IResult<&[u8], Owner<'_>> {
+    let name = |i| take_while1(|c: u8| c != b',' && c != b']')(i);
+    delimited(
+        char('['),
+        tuple((opt(terminated(name, char(','))), name)),
+        char(']'),
+    )(i)
+}
+
+// (SYSTEM,OWNER,GROUP,WORLD), each a combination of RWED.
+fn protection(i: &[u8]) -> IResult<&[u8], u32> {
+    let access = |i| take_while(|c| b"RWED".contains(&c))(i);
+    let (i, (_, owner, group, world)) = delimited(
+        char('('),
+        tuple((
+            terminated(access, char(',')),
+            terminated(access, char(',')),
+            terminated(access, char(',')),
+            access,
+        )),
+        char(')'),
+    )(i)?;
+
+    // Read maps to read, write and delete to write and execute to execute.
+    let bits = |access: &[u8]| {
+        access.iter().fold(0, |bits, c| {
+            bits | match c {
+                b'R' => 0o4,
+                b'W' | b'D' => 0o2,
+                b'E' => 0o1,
+                _ => 0,
+            }
+        })
+    };
+
+    Ok((i, bits(owner) << 6 | bits(group) << 3 | bits(world)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{DateTime, EntryKind, ListEntry};
+
+    use super::{parse_vms_line, parse_vms_listing};
+
+    #[test]
+    fn test_file() {
+        assert_eq!(
+            parse_vms_line(b"NAME.EXT;3  12/18  1-JAN-2020 10:00 [GROUP,OWNER] (RWED,RWED,RE,)"),
+            Some(ListEntry {
+                name: b"NAME.EXT".to_vec(),
+                kind: EntryKind::File,
+                size: Some(12 * 512),
+                modified: Some(DateTime::new(2020, 1, 1, 10, 0, 0)),
+                permissions: Some(0o750),
+                link_count: None,
+                owner: Some(b"OWNER".to_vec()),
+                group: Some(b"GROUP".to_vec()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_listing_with_header_and_wrapped_name() {
+        let listing = b"\r\n\
+            Directory DISK$USER:[FOO]\r\n\
+            \r\n\
+            SUBDIR.DIR;1         1/3     5-MAR-2021 08:30:12.55  [OWNER]  (RWE,RWE,RE,E)\r\n\
+            A_VERY_LONG_FILE_NAME_INDEED.TXT;12\r\n\
+            \x20                    4/6    17-NOV-2022 23:59  [OWNER]  (RWED,RWED,R,)\r\n\
+            \r\n\
+            Total of 2 files, 5/9 blocks.\r\n";
+        let entries = parse_vms_listing(listing);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, b"SUBDIR");
+        assert_eq!(entries[0].kind, EntryKind::Directory);
+        assert_eq!(
+            entries[0].modified,
+            Some(DateTime::new(2021, 3, 5, 8, 30, 12))
+        );
+        assert_eq!(entries[1].name, b"A_VERY_LONG_FILE_NAME_INDEED.TXT");
+        assert_eq!(entries[1].size, Some(4 * 512));
+    }
+}
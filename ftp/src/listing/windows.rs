@@ -0,0 +1,118 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag_no_case, take_while1},
+    character::complete::{char, space1},
+    combinator::{map, opt, rest, verify},
+    sequence::{preceded, terminated, tuple},
+    IResult,
+};
+
+use super::{digits, lines, DateTime, EntryKind, ListEntry};
+
+/// Parses the output of a `LIST` command sent by an IIS server
+/// in its default, MS-DOS like, format.
+pub fn parse_windows_listing(listing: &[u8]) -> Vec<ListEntry> {
+    lines(listing).filter_map(parse_windows_line).collect()
+}
+
+/// Parses a single IIS line such as
+/// `01-15-20  03:45PM       <DIR>          name`.
+pub fn parse_windows_line(line: &[u8]) -> Option<ListEntry> {
+    let (_, (modified, (kind, size), name)) = tuple((
+        terminated(date_time, space1),
+        terminated(
+            alt((
+                map(tag_no_case("<DIR>"), |_| (EntryKind::Directory, None)),
+                map(size, |size| (EntryKind::File, Some(size))),
+            )),
+            space1,
+        ),
+        verify(rest, |name: &[u8]| !name.is_empty()),
+    ))(line)
+    .ok()?;
+
+    Some(ListEntry {
+        name: name.to_vec(),
+        kind,
+        size,
+        modified: modified.is_valid().then_some(modified),
+        ..Default::default()
+    })
+}
+
+// MM-DD-YY HH:MM[AM|PM]
+fn date_time(i: &[u8]) -> IResult<&[u8], DateTime> {
+    let (i, (month, day, year)) = tuple((
+        terminated(digits(2, 2), char('-')),
+        terminated(digits(2, 2), char('-')),
+        alt((digits(4, 4), digits(2, 2))),
+    ))(i)?;
+    let (i, (hour, minute, meridiem)) = preceded(
+        space1,
+        tuple((
+            terminated(digits(1, 2), char(':')),
+            digits(2, 2),
+            opt(alt((tag_no_case("AM"), tag_no_case("PM")))),
+        )),
+    )(i)?;
+
+    // Two digit years follow the POSIX `strptime` convention.
+    let year: i32 = match year {
+        0..=68 => 2000 + year,
+        69..=99 => 1900 + year,
+        year => year,
+    };
+    let hour: u8 = match meridiem {
+        Some(meridiem) if meridiem.eq_ignore_ascii_case(b"PM") && hour < 12 => hour + 12,
+        Some(meridiem) if meridiem.eq_ignore_ascii_case(b"AM") && hour == 12 => 0,
+        _ => hour,
+    };
+
+    Ok((i, DateTime::new(year, month, day, hour, minute, 0)))
+}
+
+// Sizes may be grouped by thousands, e.g. "1,234,567".
+fn size(i: &[u8]) -> IResult<&[u8], u64> {
+    let (i, size) = take_while1(|c: u8| c.is_ascii_digit() || c == b',')(i)?;
+    let size = size
+        .iter()
+        .filter(|c| c.is_ascii_digit())
+        .try_fold(0u64, |size, &c| {
+            size.checked_mul(10)?.checked_add(u64::from(c - b'0'))
+        });
+
+    match size {
+        Some(size) => Ok((i, size)),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            i,
+            nom::error::ErrorKind::Digit,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{DateTime, EntryKind};
+
+    use super::parse_windows_line;
+
+    #[test]
+    fn test_directory() {
+        let entry =
+            parse_windows_line(b"01-15-20  03:45PM       <DIR>          My Documents").unwrap();
+
+        assert_eq!(entry.name, b"My Documents");
+        assert_eq!(entry.kind, EntryKind::Directory);
+        assert_eq!(entry.size, None);
+        assert_eq!(entry.modified, Some(DateTime::new(2020, 1, 15, 15, 45, 0)));
+    }
+
+    #[test]
+    fn test_file_with_four_digit_year() {
+        let entry = parse_windows_line(b"12-31-1999  12:05AM            1,234 party.txt").unwrap();
+
+        assert_eq!(entry.kind, EntryKind::File);
+        assert_eq!(entry.size, Some(1234));
+        assert_eq!(entry.modified, Some(DateTime::new(1999, 12, 31, 0, 5, 0)));
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::Write;
+
+use super::{datetime::MONTHS, DateTime, EntryKind, Fact, ListEntry};
+
+// `ls` lists the time instead of the year for files modified
+// within the last half of an average Gregorian year.
+const SIX_MONTHS: i64 = 31_556_952 / 2;
+
+/// Writes entries as `LIST` output formatted like `ls -l`.
+pub fn write_unix_listing<W: Write>(
+    writer: &mut W,
+    entries: &[ListEntry],
+    now: DateTime,
+) -> std::io::Result<()> {
+    for entry in entries {
+        writer.write_all(&format_unix_line(entry, now))?;
+        writer.write_all(b"\r\n")?;
+    }
+
+    Ok(())
+}
+
+/// Writes entries as `NLST` output, which is just their names.
+pub fn write_name_list<W: Write>(writer: &mut W, entries: &[ListEntry]) -> std::io::Result<()> {
+    for entry in entries {
+        writer.write_all(&entry.name)?;
+        writer.write_all(b"\r\n")?;
+    }
+
+    Ok(())
+}
+
+/// Writes entries as `MLSD` output listing only the selected facts.
+pub fn write_mlsd_listing<W: Write>(
+    writer: &mut W,
+    entries: &[ListEntry],
+    facts: &[Fact],
+) -> std::io::Result<()> {
+    for entry in entries {
+        writer.write_all(&format_mlsd_line(entry, facts))?;
+        writer.write_all(b"\r\n")?;
+    }
+
+    Ok(())
+}
+
+/// Formats an entry like `ls -l` would, without a line ending.
+/// Missing fields are filled in with the values `ls` would show
+/// for a file with default permissions owned by "ftp".
+pub fn format_unix_line(entry: &ListEntry, now: DateTime) -> Vec<u8> {
+    let (kind, default_permissions) = match entry.kind {
+        EntryKind::File => (b'-', 0o644),
+        EntryKind::Directory | EntryKind::CurrentDirectory | EntryKind::ParentDirectory => {
+            (b'd', 0o755)
+        }
+        EntryKind::Symlink { .. } => (b'l', 0o777),
+        EntryKind::Other => (b'-', 0o644),
+    };
+
+    let mut line = vec![kind];
+    line.extend_from_slice(&permission_string(
+        entry.permissions.unwrap_or(default_permissions),
+    ));
+    line.extend_from_slice(format!(" {:>4} ", entry.link_count.unwrap_or(1)).as_bytes());
+    pad_right(&mut line, entry.owner.as_deref().unwrap_or(b"ftp"), 8);
+    line.push(b' ');
+    pad_right(&mut line, entry.group.as_deref().unwrap_or(b"ftp"), 8);
+    line.extend_from_slice(format!(" {:>8} ", entry.size.unwrap_or(0)).as_bytes());
+
+    let modified = entry.modified.unwrap_or_default();
+    let month = MONTHS[usize::from(modified.month.clamp(1, 12)) - 1];
+    line.extend_from_slice(month);
+    line.extend_from_slice(format!(" {:>2} ", modified.day).as_bytes());
+    let age = now.timestamp() - modified.timestamp();
+    if (0..SIX_MONTHS).contains(&age) {
+        line.extend_from_slice(format!("{:02}:{:02}", modified.hour, modified.minute).as_bytes());
+    } else {
+        line.extend_from_slice(format!(" {:>4}", modified.year).as_bytes());
+    }
+
+    line.push(b' ');
+    line.extend_from_slice(&entry.name);
+    if let EntryKind::Symlink { target } = &entry.kind {
+        line.extend_from_slice(b" -> ");
+        line.extend_from_slice(target);
+    }
+
+    line
+}
+
+/// Formats an entry as a fact line of `MLSD` output such as
+/// `type=file;size=42;modify=20200101120000; name`, without a line ending.
+/// Facts the entry doesn't carry are left out.
+pub fn format_mlsd_line(entry: &ListEntry, facts: &[Fact]) -> Vec<u8> {
+    let mut line = Vec::new();
+    for fact in Fact::ALL.into_iter().filter(|fact| facts.contains(fact)) {
+        let value = match fact {
+            Fact::Type => Some(type_fact(entry)),
+            Fact::Size => entry.size.map(|size| size.to_string().into_bytes()),
+            Fact::Modify => entry.modified.map(|modified| modified.time_val()),
+            Fact::Perm => entry
+                .permissions
+                .map(|permissions| perm_fact(&entry.kind, permissions)),
+            Fact::UnixMode => entry
+                .permissions
+                .map(|permissions| format!("{permissions:04o}").into_bytes()),
+            Fact::UnixOwner => entry.owner.clone(),
+            Fact::UnixGroup => entry.group.clone(),
+        };
+
+        if let Some(value) = value {
+            line.extend_from_slice(fact.name());
+            line.push(b'=');
+            line.extend_from_slice(&value);
+            line.push(b';');
+        }
+    }
+
+    line.push(b' ');
+    line.extend_from_slice(&entry.name);
+    line
+}
+
+fn type_fact(entry: &ListEntry) -> Vec<u8> {
+    match (&entry.kind, &entry.name[..]) {
+        (EntryKind::CurrentDirectory, _) | (EntryKind::Directory, b".") => b"cdir".to_vec(),
+        (EntryKind::ParentDirectory, _) | (EntryKind::Directory, b"..") => b"pdir".to_vec(),
+        (EntryKind::Directory, _) => b"dir".to_vec(),
+        (EntryKind::File, _) => b"file".to_vec(),
+        (EntryKind::Symlink { target }, _) => [&b"OS.unix=slink:"[..], target].concat(),
+        (EntryKind::Other, _) => b"OS.unix=other".to_vec(),
+    }
+}
+
+// The permissions of the owner are the best guess of what the
+// logged in user may do, as the server's user isn't known here.
+fn perm_fact(kind: &EntryKind, permissions: u32) -> Vec<u8> {
+    let (read, write, execute) = (
+        permissions & 0o400 != 0,
+        permissions & 0o200 != 0,
+        permissions & 0o100 != 0,
+    );
+
+    let mut perm = Vec::new();
+    if kind.is_directory() {
+        if execute {
+            perm.push(b'e');
+        }
+        if read {
+            perm.push(b'l');
+        }
+        if write {
+            perm.extend_from_slice(b"cdfmp");
+        }
+    } else {
+        if write {
+            perm.extend_from_slice(b"adfw");
+        }
+        if read {
+            perm.push(b'r');
+        }
+    }
+
+    perm
+}
+
+fn permission_string(permissions: u32) -> [u8; 9] {
+    let mut string = *b"rwxrwxrwx";
+    for (index, c) in string.iter_mut().enumerate() {
+        if permissions & (1 << (8 - index)) == 0 {
+            *c = b'-';
+        }
+    }
+
+    // Setuid, setgid and sticky replace the execute bits.
+    for (index, bit, set) in [(2, 0o4000, b's'), (5, 0o2000, b's'), (8, 0o1000, b't')] {
+        if permissions & bit != 0 {
+            string[index] = if string[index] == b'-' {
+                set.to_ascii_uppercase()
+            } else {
+                set
+            };
+        }
+    }
+
+    string
+}
+
+fn pad_right(line: &mut Vec<u8>, value: &[u8], width: usize) {
+    line.extend_from_slice(value);
+    line.resize(line.len() + width.saturating_sub(value.len()), b' ');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listing::{parse_unix_line, DateTime, EntryKind, Fact, ListEntry};
+
+    use super::{format_mlsd_line, format_unix_line};
+
+    const NOW: DateTime = DateTime {
+        year: 2023,
+        month: 3,
+        day: 15,
+        hour: 12,
+        minute: 0,
+        second: 0,
+    };
+
+    fn entry(modified: DateTime) -> ListEntry {
+        ListEntry {
+            name: b"report.csv".to_vec(),
+            kind: EntryKind::File,
+            size: Some(1024),
+            modified: Some(modified),
+            permissions: Some(0o640),
+            link_count: Some(1),
+            owner: Some(b"alice".to_vec()),
+            group: Some(b"staff".to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_recent_file() {
+        assert_eq!(
+            format_unix_line(&entry(DateTime::new(2023, 3, 1, 9, 5, 0)), NOW),
+            b"-rw-r-----    1 alice    staff        1024 Mar  1 09:05 report.csv".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_old_file() {
+        assert_eq!(
+            format_unix_line(&entry(DateTime::new(2022, 9, 1, 9, 5, 0)), NOW),
+            b"-rw-r-----    1 alice    staff        1024 Sep  1  2022 report.csv".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_round_trips_through_parser() {
+        let entry = entry(DateTime::new(2023, 1, 20, 17, 45, 0));
+
+        assert_eq!(
+            parse_unix_line(&format_unix_line(&entry, NOW), NOW),
+            Some(entry)
+        );
+    }
+
+    #[test]
+    fn test_mlsd_honours_selection() {
+        assert_eq!(
+            format_mlsd_line(
+                &entry(DateTime::new(2023, 3, 1, 9, 5, 7)),
+                &[Fact::Modify, Fact::Type, Fact::Perm]
+            ),
+            b"type=file;modify=20230301090507;perm=adfwr; report.csv".to_vec()
+        );
+    }
+}
@@ -1,17 +1,19 @@
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use nom::{
     branch::alt,
-    bytes::streaming::{tag_no_case, take_while1},
-    character::streaming::u8,
-    combinator::opt,
+    bytes::streaming::{tag, tag_no_case, take, take_till1, take_while1},
+    character::streaming::{i64, u32, u8},
+    combinator::{map, opt, verify},
+    error::{Error, ErrorKind},
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
 use super::common::{comma, crlf, space};
 use crate::command::{
-    Command, FileStructureKind, FormatControl, RepresentationTypeKind, TransferModeKind,
+    Command, ExtendedPassiveKind, FileStructureKind, FormatControl, ProtectionLevel,
+    RepresentationTypeKind, TransferModeKind,
 };
 
 pub fn command(i: &[u8]) -> IResult<&[u8], Command> {
@@ -45,7 +47,7 @@ pub fn command(i: &[u8]) -> IResult<&[u8], Command> {
         // CDUP <CRLF>
         b"CDUP" => parse!(Command::ChangeToParentDirectory),
         // SMNT <SP> <pathname> <CRLF>
-        b"SNMT" => parse!(Command::StructureMount, pathname),
+        b"SMNT" => parse!(Command::StructureMount, pathname),
         // QUIT <CRLF>
         b"QUIT" => parse!(Command::Logout),
         // REIN <CRLF>
@@ -113,6 +115,34 @@ pub fn command(i: &[u8]) -> IResult<&[u8], Command> {
         b"HELP" => parse!(Command::Help, [string]),
         // NOOP <CRLF>
         b"NOOP" => parse!(Command::Noop),
+        // AUTH <SP> <mechanism-name> <CRLF>
+        b"AUTH" => parse!(Command::Authentication, mechanism_name),
+        // PBSZ <SP> <decimal-integer> <CRLF>
+        b"PBSZ" => parse!(Command::ProtectionBufferSize, u32),
+        // PROT <SP> <prot-code> <CRLF>
+        b"PROT" => parse!(Command::DataChannelProtection, prot_code),
+        // CCC <CRLF>
+        b"CCC" => parse!(Command::ClearCommandChannel),
+        // FEAT <CRLF>
+        b"FEAT" => parse!(Command::Features),
+        // OPTS <SP> <command-name> [<SP> <command-options>] <CRLF>
+        b"OPTS" => parse!(
+            |(name, options): (&[u8], Option<&[u8]>)| Command::Options(
+                name.to_vec(),
+                options.map(<[u8]>::to_vec)
+            ),
+            pair(command_name_argument, opt(preceded(space, command_options)))
+        ),
+        // EPRT <SP> <d><net-prt><d><net-addr><d><tcp-port><d> <CRLF>
+        b"EPRT" => parse!(Command::ExtendedDataPort, extended_address),
+        // EPSV [<SP> (<net-prt> | ALL)] <CRLF>
+        b"EPSV" => parse!(Command::ExtendedPassive, [extended_passive_kind]),
+        // SIZE <SP> <pathname> <CRLF>
+        b"SIZE" => parse!(Command::FileSize, pathname),
+        // MDTM <SP> <pathname> <CRLF>
+        b"MDTM" => parse!(Command::ModificationTime, pathname),
+        // MLSD [<SP> <pathname>] <CRLF>
+        b"MLSD" => parse!(Command::MachineListDirectory, [pathname]),
         _ => {
             unreachable!("All command name variants are specified by the `command_name()` function")
         }
@@ -127,28 +157,44 @@ fn command_name(i: &[u8]) -> IResult<&[u8], &[u8]> {
         tag_no_case("MKD"),
         tag_no_case("PWD"),
         tag_no_case("RMD"),
-        tag_no_case("ABOR"),
-        tag_no_case("ACCT"),
-        tag_no_case("ALLO"),
-        tag_no_case("APPE"),
-        tag_no_case("CDUP"),
-        tag_no_case("DELE"),
-        tag_no_case("HELP"),
-        tag_no_case("LIST"),
-        tag_no_case("MODE"),
-        tag_no_case("NLST"),
-        tag_no_case("NOOP"),
-        tag_no_case("PASS"),
-        tag_no_case("PASV"),
-        tag_no_case("PORT"),
-        tag_no_case("QUIT"),
-        tag_no_case("REIN"),
+        tag_no_case("CCC"),
+        // `alt` takes at most 21 parsers, so four character commands are grouped.
+        alt((
+            tag_no_case("ABOR"),
+            tag_no_case("ACCT"),
+            tag_no_case("ALLO"),
+            tag_no_case("APPE"),
+            tag_no_case("AUTH"),
+            tag_no_case("CDUP"),
+            tag_no_case("DELE"),
+            tag_no_case("EPRT"),
+            tag_no_case("EPSV"),
+            tag_no_case("FEAT"),
+            tag_no_case("HELP"),
+            tag_no_case("LIST"),
+            tag_no_case("MDTM"),
+            tag_no_case("MLSD"),
+            tag_no_case("MODE"),
+        )),
         alt((
+            tag_no_case("NLST"),
+            tag_no_case("NOOP"),
+            tag_no_case("OPTS"),
+            tag_no_case("PASS"),
+            tag_no_case("PASV"),
+            tag_no_case("PBSZ"),
+            tag_no_case("PORT"),
+            tag_no_case("PROT"),
+            tag_no_case("QUIT"),
+            tag_no_case("REIN"),
             tag_no_case("REST"),
             tag_no_case("RETR"),
             tag_no_case("RNFR"),
             tag_no_case("RNTO"),
+        )),
+        alt((
             tag_no_case("SITE"),
+            tag_no_case("SIZE"),
             tag_no_case("SMNT"),
             tag_no_case("STAT"),
             tag_no_case("STOR"),
@@ -229,6 +275,37 @@ fn port_number(i: &[u8]) -> IResult<&[u8], u16> {
     Ok((i, u16::from_be_bytes([a, b])))
 }
 
+// <d><net-prt><d><net-addr><d><tcp-port><d>, where <d> is any <pr-char>
+fn extended_address(i: &[u8]) -> IResult<&[u8], SocketAddr> {
+    let start = i;
+    let (i, delimiter) = verify(take(1usize), |d: &[u8]| is_pr_char(d[0]))(i)?;
+    let field = |i| terminated(take_till1(|c| c == delimiter[0]), tag(delimiter))(i);
+    let (i, (protocol, address, port)) = tuple((field, field, field))(i)?;
+
+    let address = std::str::from_utf8(address).ok();
+    let ip = match protocol {
+        b"1" => address.and_then(|address| address.parse().ok().map(IpAddr::V4)),
+        b"2" => address.and_then(|address| address.parse::<Ipv6Addr>().ok().map(IpAddr::V6)),
+        _ => None,
+    };
+    let port = std::str::from_utf8(port)
+        .ok()
+        .and_then(|port| port.parse().ok());
+
+    match (ip, port) {
+        (Some(ip), Some(port)) => Ok((i, SocketAddr::new(ip, port))),
+        _ => Err(nom::Err::Error(Error::new(start, ErrorKind::Verify))),
+    }
+}
+
+// <net-prt> | ALL
+fn extended_passive_kind(i: &[u8]) -> IResult<&[u8], ExtendedPassiveKind> {
+    alt((
+        map(tag_no_case(b"ALL"), |_| ExtendedPassiveKind::All),
+        map(u8, ExtendedPassiveKind::Protocol),
+    ))(i)
+}
+
 // <number> ::= any decimal integer 1 through 255
 fn number(i: &[u8]) -> IResult<&[u8], u8> {
     u8(i)
@@ -294,9 +371,14 @@ fn structure_code(i: &[u8]) -> IResult<&[u8], FileStructureKind> {
     ))
 }
 
-// <mode-code> ::= S | B | C
+// <mode-code> ::= S | B | C | Z
 fn mode_code(i: &[u8]) -> IResult<&[u8], TransferModeKind> {
-    let (i, code) = alt((tag_no_case(b"S"), tag_no_case(b"B"), tag_no_case(b"C")))(i)?;
+    let (i, code) = alt((
+        tag_no_case(b"S"),
+        tag_no_case(b"B"),
+        tag_no_case(b"C"),
+        tag_no_case(b"Z"),
+    ))(i)?;
 
     Ok((
         i,
@@ -304,11 +386,48 @@ fn mode_code(i: &[u8]) -> IResult<&[u8], TransferModeKind> {
             b"s" | b"S" => TransferModeKind::Stream,
             b"b" | b"B" => TransferModeKind::Block,
             b"c" | b"C" => TransferModeKind::Compressed,
+            b"z" | b"Z" => TransferModeKind::Deflate,
             _ => unreachable!("All options should be exhausted by the previous parser"),
         },
     ))
 }
 
+// <mechanism-name> ::= <string>
+fn mechanism_name(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    string(i)
+}
+
+// <prot-code> ::= C | S | E | P
+fn prot_code(i: &[u8]) -> IResult<&[u8], ProtectionLevel> {
+    let (i, code) = alt((
+        tag_no_case(b"C"),
+        tag_no_case(b"S"),
+        tag_no_case(b"E"),
+        tag_no_case(b"P"),
+    ))(i)?;
+
+    Ok((
+        i,
+        match code {
+            b"c" | b"C" => ProtectionLevel::Clear,
+            b"s" | b"S" => ProtectionLevel::Safe,
+            b"e" | b"E" => ProtectionLevel::Confidential,
+            b"p" | b"P" => ProtectionLevel::Private,
+            _ => unreachable!("All options should be exhausted by the previous parser"),
+        },
+    ))
+}
+
+// <command-name> ::= <pr-string>
+fn command_name_argument(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    pr_string(i)
+}
+
+// <command-options> ::= <string>
+fn command_options(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    string(i)
+}
+
 // <pathname> ::= <string>
 fn pathname(i: &[u8]) -> IResult<&[u8], &[u8]> {
     string(i)
@@ -316,7 +435,7 @@ fn pathname(i: &[u8]) -> IResult<&[u8], &[u8]> {
 
 // <decimal-integer> ::= any decimal integer
 fn decimal_integer(i: &[u8]) -> IResult<&[u8], i64> {
-    nom::number::streaming::be_i64(i)
+    i64(i)
 }
 
 #[cfg(test)]
@@ -326,7 +445,9 @@ mod tests {
         Err,
     };
 
-    use crate::command::Command;
+    use std::net::SocketAddr;
+
+    use crate::command::{Command, ExtendedPassiveKind, ProtectionLevel};
 
     use super::{command, number, port_number};
 
@@ -396,6 +517,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extended_data_port() {
+        test_parser_success!(
+            b"EPRT |1|132.235.1.2|6275|\r\n",
+            command,
+            Command::ExtendedDataPort(SocketAddr::from(([132, 235, 1, 2], 6275)))
+        );
+        test_parser_success!(
+            b"EPRT !2!1080::8:800:200C:417A!5282!\r\n",
+            command,
+            Command::ExtendedDataPort("[1080::8:800:200c:417a]:5282".parse().unwrap())
+        );
+        assert!(command(b"EPRT |2|132.235.1.2|6275|\r\n").is_err());
+    }
+
+    #[test]
+    fn test_extended_passive() {
+        test_parser_success!(b"EPSV\r\n", command, Command::ExtendedPassive(None));
+        test_parser_success!(
+            b"EPSV all\r\n",
+            command,
+            Command::ExtendedPassive(Some(ExtendedPassiveKind::All))
+        );
+        test_parser_success!(
+            b"EPSV 2\r\n",
+            command,
+            Command::ExtendedPassive(Some(ExtendedPassiveKind::Protocol(2)))
+        );
+    }
+
+    #[test]
+    fn test_security_commands() {
+        test_parser_success!(
+            b"AUTH TLS\r\n",
+            command,
+            Command::Authentication(b"TLS".to_vec())
+        );
+        test_parser_success!(b"PBSZ 0\r\n", command, Command::ProtectionBufferSize(0));
+        test_parser_success!(
+            b"prot p\r\n",
+            command,
+            Command::DataChannelProtection(ProtectionLevel::Private)
+        );
+        test_parser_success!(b"CCC\r\n", command, Command::ClearCommandChannel);
+    }
+
+    #[test]
+    fn test_file_status_commands() {
+        test_parser_success!(
+            b"SIZE a.bin\r\n",
+            command,
+            Command::FileSize(b"a.bin".to_vec())
+        );
+        test_parser_success!(
+            b"mdtm a.bin\r\n",
+            command,
+            Command::ModificationTime(b"a.bin".to_vec())
+        );
+        test_parser_success!(b"FEAT\r\n", command, Command::Features);
+        test_parser_success!(b"MLSD\r\n", command, Command::MachineListDirectory(None));
+        test_parser_success!(
+            b"MLSD docs\r\n",
+            command,
+            Command::MachineListDirectory(Some(b"docs".to_vec()))
+        );
+    }
+
     #[test]
     fn test_port() {
         test_parser_success!(b"132,219\r\n", port_number, 34011, b"\r\n");
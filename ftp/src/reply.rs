@@ -1,16 +1,647 @@
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::listing::DateTime;
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub struct Reply {
+    #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = code))]
     pub code: [u8; 3],
     pub text: Text,
 }
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 pub enum Text {
     SingleLine {
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = line))]
         line: Vec<u8>,
     },
     MultiLine {
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = lines))]
         lines: Vec<Vec<u8>>,
+        #[cfg_attr(any(test, feature = "arbitrary"), arbitrary(with = line))]
         last_line: Vec<u8>,
     },
 }
+
+impl Reply {
+    /// Creates a single line reply.
+    pub fn new(code: [u8; 3], line: impl Into<Vec<u8>>) -> Self {
+        Self {
+            code,
+            text: Text::SingleLine { line: line.into() },
+        }
+    }
+
+    /// Creates a builder for a reply that may span multiple lines.
+    pub fn builder(code: [u8; 3]) -> ReplyBuilder {
+        ReplyBuilder::new(code)
+    }
+}
+
+/// Builds a `Reply` line by line, choosing between a single line
+/// and a multi-line text depending on the amount of lines added.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ReplyBuilder {
+    code: [u8; 3],
+    lines: Vec<Vec<u8>>,
+}
+
+impl ReplyBuilder {
+    pub fn new(code: [u8; 3]) -> Self {
+        Self {
+            code,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn line(mut self, line: impl Into<Vec<u8>>) -> Self {
+        self.lines.push(line.into());
+        self
+    }
+
+    pub fn lines<I>(mut self, lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Vec<u8>>,
+    {
+        self.lines.extend(lines.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the last line of the reply, which is the one carrying the
+    /// reply code on a multi-line reply.
+    pub fn last_line(self, line: impl Into<Vec<u8>>) -> Reply {
+        self.line(line).build()
+    }
+
+    pub fn build(mut self) -> Reply {
+        let text = match self.lines.pop() {
+            None => Text::SingleLine { line: Vec::new() },
+            Some(line) if self.lines.is_empty() => Text::SingleLine { line },
+            Some(last_line) => Text::MultiLine {
+                lines: self.lines,
+                last_line,
+            },
+        };
+
+        Reply {
+            code: self.code,
+            text,
+        }
+    }
+}
+
+// Standard replies according to RFC 959 section 4.2
+impl Reply {
+    // 110 Restart marker reply.
+    pub fn restart_marker(marker: &[u8]) -> Self {
+        let mut line = b"MARK ".to_vec();
+        line.extend_from_slice(marker);
+        line.extend_from_slice(b" = ");
+        line.extend_from_slice(marker);
+        Self::new(*b"110", line)
+    }
+
+    // 120 Service ready in nnn minutes.
+    pub fn service_ready_in(minutes: u32) -> Self {
+        Self::new(*b"120", format!("Service ready in {minutes} minutes."))
+    }
+
+    // 125 Data connection already open; transfer starting.
+    pub fn data_connection_already_open() -> Self {
+        Self::new(*b"125", "Data connection already open; transfer starting.")
+    }
+
+    // 150 File status okay; about to open data connection.
+    pub fn file_status_okay() -> Self {
+        Self::new(*b"150", "File status okay; about to open data connection.")
+    }
+
+    // 200 Command okay.
+    pub fn command_okay() -> Self {
+        Self::new(*b"200", "Command okay.")
+    }
+
+    // 202 Command not implemented, superfluous at this site.
+    pub fn command_superfluous() -> Self {
+        Self::new(
+            *b"202",
+            "Command not implemented, superfluous at this site.",
+        )
+    }
+
+    // 211 System status, or system help reply.
+    pub fn system_status(status: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"211", status)
+    }
+
+    // 212 Directory status.
+    pub fn directory_status(status: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"212", status)
+    }
+
+    // 213 File status.
+    pub fn file_status(status: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"213", status)
+    }
+
+    // 214 Help message.
+    pub fn help_message(message: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"214", message)
+    }
+
+    // 215 NAME system type.
+    pub fn system_type(name: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"215", name)
+    }
+
+    // 220 Service ready for new user.
+    pub fn service_ready() -> Self {
+        Self::new(*b"220", "Service ready for new user.")
+    }
+
+    // 221 Service closing control connection.
+    pub fn service_closing() -> Self {
+        Self::new(*b"221", "Service closing control connection.")
+    }
+
+    // 225 Data connection open; no transfer in progress.
+    pub fn data_connection_open() -> Self {
+        Self::new(*b"225", "Data connection open; no transfer in progress.")
+    }
+
+    // 226 Closing data connection.
+    pub fn closing_data_connection() -> Self {
+        Self::new(*b"226", "Closing data connection.")
+    }
+
+    // 227 Entering Passive Mode (h1,h2,h3,h4,p1,p2).
+    pub fn entering_passive_mode(address: SocketAddrV4) -> Self {
+        let [h1, h2, h3, h4] = address.ip().octets();
+        let [p1, p2] = address.port().to_be_bytes();
+        Self::new(
+            *b"227",
+            format!("Entering Passive Mode ({h1},{h2},{h3},{h4},{p1},{p2})."),
+        )
+    }
+
+    // 229 Entering Extended Passive Mode (|||port|).
+    pub fn entering_extended_passive_mode(port: u16) -> Self {
+        Self::new(
+            *b"229",
+            format!("Entering Extended Passive Mode (|||{port}|)."),
+        )
+    }
+
+    // 230 User logged in, proceed.
+    pub fn user_logged_in() -> Self {
+        Self::new(*b"230", "User logged in, proceed.")
+    }
+
+    // 234 Security data exchange complete.
+    pub fn security_exchange_complete() -> Self {
+        Self::new(*b"234", "Security data exchange complete.")
+    }
+
+    // 250 Requested file action okay, completed.
+    pub fn file_action_okay() -> Self {
+        Self::new(*b"250", "Requested file action okay, completed.")
+    }
+
+    // 257 "PATHNAME" created.
+    pub fn pathname_created(pathname: &[u8]) -> Self {
+        Self::new(*b"257", quote_pathname(pathname, b" created."))
+    }
+
+    // 257 "PATHNAME" is the current directory.
+    pub fn current_directory(pathname: &[u8]) -> Self {
+        Self::new(
+            *b"257",
+            quote_pathname(pathname, b" is the current directory."),
+        )
+    }
+
+    // 331 User name okay, need password.
+    pub fn need_password() -> Self {
+        Self::new(*b"331", "User name okay, need password.")
+    }
+
+    // 332 Need account for login.
+    pub fn need_account_for_login() -> Self {
+        Self::new(*b"332", "Need account for login.")
+    }
+
+    // 350 Requested file action pending further information.
+    pub fn file_action_pending() -> Self {
+        Self::new(
+            *b"350",
+            "Requested file action pending further information.",
+        )
+    }
+
+    // 421 Service not available, closing control connection.
+    pub fn service_not_available() -> Self {
+        Self::new(
+            *b"421",
+            "Service not available, closing control connection.",
+        )
+    }
+
+    // 425 Can't open data connection.
+    pub fn cant_open_data_connection() -> Self {
+        Self::new(*b"425", "Can't open data connection.")
+    }
+
+    // 426 Connection closed; transfer aborted.
+    pub fn transfer_aborted() -> Self {
+        Self::new(*b"426", "Connection closed; transfer aborted.")
+    }
+
+    // 450 Requested file action not taken.
+    pub fn file_unavailable() -> Self {
+        Self::new(
+            *b"450",
+            "Requested file action not taken. File unavailable.",
+        )
+    }
+
+    // 451 Requested action aborted: local error in processing.
+    pub fn local_error() -> Self {
+        Self::new(
+            *b"451",
+            "Requested action aborted: local error in processing.",
+        )
+    }
+
+    // 452 Requested action not taken. Insufficient storage space in system.
+    pub fn insufficient_storage() -> Self {
+        Self::new(
+            *b"452",
+            "Requested action not taken. Insufficient storage space in system.",
+        )
+    }
+
+    // 500 Syntax error, command unrecognized.
+    pub fn command_unrecognized() -> Self {
+        Self::new(*b"500", "Syntax error, command unrecognized.")
+    }
+
+    // 501 Syntax error in parameters or arguments.
+    pub fn syntax_error_in_parameters() -> Self {
+        Self::new(*b"501", "Syntax error in parameters or arguments.")
+    }
+
+    // 502 Command not implemented.
+    pub fn command_not_implemented() -> Self {
+        Self::new(*b"502", "Command not implemented.")
+    }
+
+    // 503 Bad sequence of commands.
+    pub fn bad_sequence_of_commands() -> Self {
+        Self::new(*b"503", "Bad sequence of commands.")
+    }
+
+    // 504 Command not implemented for that parameter.
+    pub fn parameter_not_implemented() -> Self {
+        Self::new(*b"504", "Command not implemented for that parameter.")
+    }
+
+    // 530 Not logged in.
+    pub fn not_logged_in() -> Self {
+        Self::new(*b"530", "Not logged in.")
+    }
+
+    // 532 Need account for storing files.
+    pub fn need_account_for_storing() -> Self {
+        Self::new(*b"532", "Need account for storing files.")
+    }
+
+    // 550 Requested action not taken. File unavailable.
+    pub fn action_not_taken() -> Self {
+        Self::new(*b"550", "Requested action not taken. File unavailable.")
+    }
+
+    // 551 Requested action aborted: page type unknown.
+    pub fn page_type_unknown() -> Self {
+        Self::new(*b"551", "Requested action aborted: page type unknown.")
+    }
+
+    // 552 Requested file action aborted. Exceeded storage allocation.
+    pub fn exceeded_storage_allocation() -> Self {
+        Self::new(
+            *b"552",
+            "Requested file action aborted. Exceeded storage allocation.",
+        )
+    }
+
+    // 553 Requested action not taken. File name not allowed.
+    pub fn file_name_not_allowed() -> Self {
+        Self::new(
+            *b"553",
+            "Requested action not taken. File name not allowed.",
+        )
+    }
+}
+
+// Reply categories according to RFC 959 section 4.2
+impl Reply {
+    // 1yz Positive Preliminary reply.
+    pub fn is_preliminary(&self) -> bool {
+        self.code[0] == b'1'
+    }
+
+    // 2yz Positive Completion reply.
+    pub fn is_completion(&self) -> bool {
+        self.code[0] == b'2'
+    }
+
+    // 3yz Positive Intermediate reply.
+    pub fn is_intermediate(&self) -> bool {
+        self.code[0] == b'3'
+    }
+
+    // 4yz Transient Negative Completion reply.
+    pub fn is_transient_negative(&self) -> bool {
+        self.code[0] == b'4'
+    }
+
+    // 5yz Permanent Negative Completion reply.
+    pub fn is_permanent_negative(&self) -> bool {
+        self.code[0] == b'5'
+    }
+}
+
+impl Reply {
+    /// Every line of the text, the last line included.
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        let (lines, last_line) = match &self.text {
+            Text::SingleLine { line } => (&[][..], line),
+            Text::MultiLine { lines, last_line } => (&lines[..], last_line),
+        };
+
+        lines
+            .iter()
+            .chain(std::iter::once(last_line))
+            .map(Vec::as_slice)
+    }
+
+    /// The address of a 227 reply. As RFC 1123 section 4.1.2.6 advises,
+    /// the text is scanned for the numbers instead of expecting parentheses.
+    pub fn passive_address(&self) -> Option<SocketAddrV4> {
+        self.lines().find_map(|line| {
+            let start = line.iter().position(u8::is_ascii_digit)?;
+            let numbers: Vec<u8> = line[start..]
+                .split(|&c| c == b',')
+                .map(|number| {
+                    let number = number.trim_ascii_start();
+                    let end = number
+                        .iter()
+                        .position(|c| !c.is_ascii_digit())
+                        .unwrap_or(number.len());
+                    std::str::from_utf8(&number[..end]).ok()?.parse().ok()
+                })
+                .take(6)
+                .collect::<Option<_>>()?;
+            let [h1, h2, h3, h4, p1, p2] = numbers[..] else {
+                return None;
+            };
+
+            Some(SocketAddrV4::new(
+                Ipv4Addr::new(h1, h2, h3, h4),
+                u16::from_be_bytes([p1, p2]),
+            ))
+        })
+    }
+
+    /// The port of a 229 reply, given in parentheses as `(<d><d><d><port><d>)`
+    /// according to RFC 2428, where `<d>` is a delimiter such as `|`.
+    pub fn extended_passive_port(&self) -> Option<u16> {
+        self.lines().find_map(|line| {
+            let start = line.iter().position(|&c| c == b'(')?;
+            let (&delimiter, rest) = line[start + 1..].split_first()?;
+            let rest = rest.strip_prefix(&[delimiter, delimiter])?;
+            let end = rest.iter().position(|&c| c == delimiter)?;
+            if rest.get(end + 1) != Some(&b')') {
+                return None;
+            }
+
+            std::str::from_utf8(&rest[..end]).ok()?.parse().ok()
+        })
+    }
+
+    /// The size in octets of a 213 reply to a `SIZE` command.
+    pub fn size(&self) -> Option<u64> {
+        let line = self.lines().next()?;
+        std::str::from_utf8(line).ok()?.trim().parse().ok()
+    }
+
+    /// The time of a 213 reply to an `MDTM` command, in UTC.
+    pub fn modification_time(&self) -> Option<DateTime> {
+        DateTime::from_time_val(self.lines().next()?.trim_ascii())
+    }
+
+    /// The features listed by a 211 reply to a `FEAT` command according to
+    /// RFC 2389, such as `MDTM` or `MLST type*;size*;`, each line after the
+    /// first starting with a space.
+    pub fn features(&self) -> Vec<Vec<u8>> {
+        let Text::MultiLine { lines, .. } = &self.text else {
+            return Vec::new();
+        };
+
+        lines
+            .iter()
+            .skip(1)
+            .filter_map(|line| line.strip_prefix(b" "))
+            .map(|feature| feature.trim_ascii().to_vec())
+            .collect()
+    }
+
+    /// The quoted pathname starting the first line of a 257 reply,
+    /// with embedded quotes undoubled.
+    pub fn pathname(&self) -> Option<Vec<u8>> {
+        let line = self.lines().next()?;
+        let mut quoted = line.strip_prefix(b"\"")?.iter();
+        let mut pathname = Vec::new();
+        while let Some(&c) = quoted.next() {
+            if c == b'"' {
+                match quoted.next() {
+                    Some(b'"') => {}
+                    _ => return Some(pathname),
+                }
+            }
+
+            pathname.push(c);
+        }
+
+        // The closing quote is missing.
+        None
+    }
+}
+
+/// Quotes a pathname according to RFC 959 appendix II,
+/// doubling any quote character embedded in it.
+fn quote_pathname(pathname: &[u8], suffix: &[u8]) -> Vec<u8> {
+    let mut line = Vec::with_capacity(pathname.len() + suffix.len() + 2);
+    line.push(b'"');
+    for &c in pathname {
+        if c == b'"' {
+            line.push(b'"');
+        }
+
+        line.push(c);
+    }
+
+    line.push(b'"');
+    line.extend_from_slice(suffix);
+    line
+}
+
+#[cfg(any(test, feature = "arbitrary"))]
+fn code(u: &mut arbitrary::Unstructured) -> arbitrary::Result<[u8; 3]> {
+    Ok([
+        u.int_in_range(b'1'..=b'6')?,
+        u.int_in_range(b'0'..=b'9')?,
+        u.int_in_range(b'0'..=b'9')?,
+    ])
+}
+
+// Any bytes except line breaks, which can't be represented inside a line.
+#[cfg(any(test, feature = "arbitrary"))]
+fn line(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Vec<u8>> {
+    let mut line: Vec<u8> = u.arbitrary()?;
+    line.retain(|&c| c != b'\r' && c != b'\n');
+    Ok(line)
+}
+
+// A multi-line text has at least its first line before the last one.
+#[cfg(any(test, feature = "arbitrary"))]
+fn lines(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Vec<Vec<u8>>> {
+    let mut lines = vec![line(u)?];
+    for _ in 0..u.arbitrary_len::<Vec<u8>>()? {
+        lines.push(line(u)?);
+    }
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::{Reply, Text};
+    use crate::listing::DateTime;
+
+    #[test]
+    fn test_builder_single_line() {
+        assert_eq!(
+            Reply::builder(*b"200").line("Command okay.").build(),
+            Reply::command_okay()
+        );
+    }
+
+    #[test]
+    fn test_builder_multi_line() {
+        assert_eq!(
+            Reply::builder(*b"211")
+                .line("Features:")
+                .lines([" MDTM", " SIZE"])
+                .last_line("End"),
+            Reply {
+                code: *b"211",
+                text: Text::MultiLine {
+                    lines: vec![b"Features:".to_vec(), b" MDTM".to_vec(), b" SIZE".to_vec()],
+                    last_line: b"End".to_vec(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_entering_passive_mode() {
+        assert_eq!(
+            Reply::entering_passive_mode(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 34011)),
+            Reply::new(*b"227", "Entering Passive Mode (10,0,0,1,132,219).")
+        );
+    }
+
+    #[test]
+    fn test_pathname_created_quoting() {
+        assert_eq!(
+            Reply::pathname_created(b"/a \"b\""),
+            Reply::new(*b"257", "\"/a \"\"b\"\"\" created.")
+        );
+    }
+
+    #[test]
+    fn test_passive_address() {
+        let address = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 34011);
+
+        assert_eq!(
+            Reply::entering_passive_mode(address).passive_address(),
+            Some(address)
+        );
+        assert_eq!(
+            Reply::new(*b"227", "Entering passive mode 10,0,0,1,132,219").passive_address(),
+            Some(address)
+        );
+        assert_eq!(
+            Reply::new(*b"227", "Entering Passive Mode (10, 0, 0, 1, 132, 219)").passive_address(),
+            Some(address)
+        );
+        assert_eq!(
+            Reply::new(*b"227", "Entering passive mode (10,0,0,1,132)").passive_address(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_file_status() {
+        assert_eq!(Reply::file_status("1048576").size(), Some(1_048_576));
+        assert_eq!(
+            Reply::file_status("20200101120005.25").modification_time(),
+            Some(DateTime::new(2020, 1, 1, 12, 0, 5))
+        );
+        assert_eq!(Reply::file_status("unknown").size(), None);
+    }
+
+    #[test]
+    fn test_features() {
+        let reply = Reply::builder(*b"211")
+            .line("Extensions supported:")
+            .lines([" MDTM", " MLST type*;size*;modify*;"])
+            .last_line("END");
+        assert_eq!(
+            reply.features(),
+            [&b"MDTM"[..], b"MLST type*;size*;modify*;"]
+        );
+        assert!(Reply::new(*b"211", "No features").features().is_empty());
+    }
+
+    #[test]
+    fn test_extended_passive_port() {
+        assert_eq!(
+            Reply::entering_extended_passive_mode(6446).extended_passive_port(),
+            Some(6446)
+        );
+        assert_eq!(
+            Reply::new(*b"229", "Entering Extended Passive Mode (!!!6446!)")
+                .extended_passive_port(),
+            Some(6446)
+        );
+        assert_eq!(
+            Reply::new(*b"229", "Entering Extended Passive Mode (|||6446)").extended_passive_port(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pathname() {
+        assert_eq!(
+            Reply::pathname_created(b"/a \"b\"").pathname(),
+            Some(b"/a \"b\"".to_vec())
+        );
+        assert_eq!(Reply::new(*b"257", "\"/unterminated").pathname(), None);
+    }
+}
@@ -21,6 +21,11 @@ impl<W: Write> CommandSerializer<W> {
                     .as_bytes()
                     .serialize(&mut self.writer)?;
             }};
+            ($command: tt, [$value: expr]) => {{
+                // Optional values write their own leading space.
+                serialize!($command);
+                $value.serialize(&mut self.writer)?;
+            }};
             ($command: tt, $value: expr) => {{
                 serialize!($command);
                 b" ".serialize(&mut self.writer)?;
@@ -61,13 +66,27 @@ impl<W: Write> CommandSerializer<W> {
             Command::RemoveDirectory(pathname) => serialize!(RMD, pathname),
             Command::MakeDirectory(pathname) => serialize!(MKD, pathname),
             Command::PrintWorkingDirectory => serialize!(PWD),
-            Command::List(pathname) => serialize!(LIST, pathname),
-            Command::NameList(pathname) => serialize!(NLST, pathname),
+            Command::List(pathname) => serialize!(LIST, [pathname]),
+            Command::NameList(pathname) => serialize!(NLST, [pathname]),
             Command::SiteParameters(parameters) => serialize!(SITE, parameters),
             Command::System => serialize!(SYST),
-            Command::Status(pathname) => serialize!(STAT, pathname),
-            Command::Help(command) => serialize!(HELP, command),
+            Command::Status(pathname) => serialize!(STAT, [pathname]),
+            Command::Help(command) => serialize!(HELP, [command]),
             Command::Noop => serialize!(NOOP),
+            Command::Authentication(mechanism) => serialize!(AUTH, mechanism),
+            Command::ProtectionBufferSize(size) => serialize!(PBSZ, size),
+            Command::DataChannelProtection(level) => serialize!(PROT, level),
+            Command::ClearCommandChannel => serialize!(CCC),
+            Command::Features => serialize!(FEAT),
+            Command::Options(name, options) => {
+                serialize!(OPTS, name);
+                options.serialize(&mut self.writer)?;
+            }
+            Command::ExtendedDataPort(address) => serialize!(EPRT, address),
+            Command::ExtendedPassive(kind) => serialize!(EPSV, [kind]),
+            Command::FileSize(pathname) => serialize!(SIZE, pathname),
+            Command::ModificationTime(pathname) => serialize!(MDTM, pathname),
+            Command::MachineListDirectory(pathname) => serialize!(MLSD, [pathname]),
         }
 
         self.writer.write_all(b"\r\n")
@@ -75,8 +94,47 @@ impl<W: Write> CommandSerializer<W> {
 }
 
 impl<W: Write> Serializer<Command> for CommandSerializer<W> {
+    /// Sends the command at once, refusing arguments with line
+    /// breaks, which would send further commands.
     fn serialize(&mut self, value: &Command) -> std::io::Result<()> {
-        self.serialize_command(value)?;
+        let mut serialized = Vec::new();
+        CommandSerializer::new(&mut serialized).serialize_command(value)?;
+        if serialized[..serialized.len() - 2]
+            .iter()
+            .any(|&c| c == b'\r' || c == b'\n')
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "command arguments must not contain line breaks",
+            ));
+        }
+
+        self.writer.write_all(&serialized)?;
         self.writer.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::command::Command;
+
+    use super::{CommandSerializer, Serializer};
+
+    #[test]
+    fn test_line_breaks_are_refused() {
+        let mut output = Vec::new();
+        let mut serializer = CommandSerializer::new(&mut output);
+
+        for argument in [&b"a\r\nDELE x"[..], b"a\nb", b"a\r"] {
+            let error = serializer
+                .serialize(&Command::ChangeWorkingDirectory(argument.to_vec()))
+                .unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+
+        serializer
+            .serialize(&Command::ChangeWorkingDirectory(b"a b".to_vec()))
+            .unwrap();
+        assert_eq!(output, b"CWD a b\r\n");
+    }
+}
@@ -1,8 +1,9 @@
 mod command;
 mod reply;
 mod serializeable;
+#[allow(clippy::module_inception)]
 mod serializer;
 
 pub use command::CommandSerializer;
-pub use reply::ReplySerializer;
+pub use reply::{MultiLineMode, ReplySerializer};
 pub use serializer::Serializer;
@@ -4,37 +4,87 @@ use crate::reply::{Reply, Text};
 
 use super::Serializer;
 
+/// Controls how intermediate lines of a multi-line reply are written.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub enum MultiLineMode {
+    /// Pads every intermediate line starting with a 3 digit number with a
+    /// leading space, as suggested by RFC 959 section 4.2.
+    #[default]
+    Escaped,
+    /// Writes intermediate lines as they are, which reproduces replies
+    /// parsed from servers that prefix every line with "code-".
+    /// Only lines which would terminate the reply early are padded.
+    Lossless,
+}
+
 pub struct ReplySerializer<W: Write> {
     writer: W,
+    mode: MultiLineMode,
 }
 
 impl<W: Write> ReplySerializer<W> {
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self::with_mode(writer, MultiLineMode::default())
+    }
+
+    pub fn with_mode(writer: W, mode: MultiLineMode) -> Self {
+        Self { writer, mode }
     }
 
     pub fn serialize_reply(&mut self, value: &Reply) -> std::io::Result<()> {
-        self.writer.write_all(&value.code)?;
-        match &value.text {
-            Text::SingleLine { line } => {
-                self.writer.write_all(b" ")?;
-                self.writer.write_all(line)?;
-            }
-            Text::MultiLine { lines, last_line } => {
-                self.writer.write_all(b"-")?;
-                for line in lines {
-                    self.writer.write_all(line)?;
-                    self.writer.write_all(b"\r\n")?;
+        // A multi-line reply without intermediate lines can only
+        // be represented on the wire as a single line reply.
+        let (lines, last_line): (&[Vec<u8>], &[u8]) = match &value.text {
+            Text::SingleLine { line } => (&[], line),
+            Text::MultiLine { lines, last_line } => (lines, last_line),
+        };
+
+        // Validate before writing anything so an invalid reply
+        // doesn't leave a partial reply on the connection.
+        for line in lines {
+            validate_line(line)?;
+        }
+
+        validate_line(last_line)?;
+
+        if !lines.is_empty() {
+            self.writer.write_all(&value.code)?;
+            self.writer.write_all(b"-")?;
+            for (index, line) in lines.iter().enumerate() {
+                // The first line follows the "code-" prefix and can't be
+                // mistaken for the end of the reply.
+                if index > 0 && self.needs_padding(&value.code, line) {
+                    self.writer.write_all(b" ")?;
                 }
 
-                self.writer.write_all(&value.code)?;
-                self.writer.write_all(b" ")?;
-                self.writer.write_all(last_line)?;
+                self.writer.write_all(line)?;
+                self.writer.write_all(b"\r\n")?;
             }
         }
 
+        self.writer.write_all(&value.code)?;
+        self.writer.write_all(b" ")?;
+        self.writer.write_all(last_line)?;
         self.writer.write_all(b"\r\n")
     }
+
+    fn needs_padding(&self, code: &[u8; 3], line: &[u8]) -> bool {
+        match self.mode {
+            MultiLineMode::Escaped => line.len() >= 3 && line[..3].iter().all(u8::is_ascii_digit),
+            MultiLineMode::Lossless => line.starts_with(code) && line.get(3) == Some(&b' '),
+        }
+    }
+}
+
+fn validate_line(line: &[u8]) -> std::io::Result<()> {
+    if line.iter().any(|&c| c == b'\r' || c == b'\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "reply text must not contain line breaks",
+        ));
+    }
+
+    Ok(())
 }
 
 impl<W: Write> Serializer<Reply> for ReplySerializer<W> {
@@ -43,3 +93,49 @@ impl<W: Write> Serializer<Reply> for ReplySerializer<W> {
         self.writer.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_reply, reply::Reply};
+
+    use super::{MultiLineMode, ReplySerializer};
+
+    fn serialize(reply: &Reply, mode: MultiLineMode) -> std::io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        ReplySerializer::with_mode(&mut output, mode).serialize_reply(reply)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_escapes_line_starting_with_code() {
+        let reply = Reply::builder(*b"226")
+            .lines(["Transfer log:", "226 bytes sent"])
+            .last_line("Done");
+        let output = serialize(&reply, MultiLineMode::Escaped).unwrap();
+
+        assert_eq!(
+            output,
+            b"226-Transfer log:\r\n 226 bytes sent\r\n226 Done\r\n".to_vec()
+        );
+        assert_eq!(parse_reply(&output).unwrap().0, b"");
+    }
+
+    #[test]
+    fn test_rejects_line_breaks() {
+        let reply = Reply::new(*b"200", "two\r\nlines");
+        let error = serialize(&reply, MultiLineMode::Escaped).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_lossless_reproduces_code_prefixed_lines() {
+        let input = b"211-Features:\r\n211-MDTM\r\n211-SIZE\r\n211 End\r\n";
+        let (_, reply) = parse_reply(input).unwrap();
+
+        assert_eq!(
+            serialize(&reply, MultiLineMode::Lossless).unwrap(),
+            input.to_vec()
+        );
+    }
+}
@@ -1,6 +1,12 @@
-use std::{io::Write, net::Ipv4Addr};
+use std::{
+    io::Write,
+    net::{Ipv4Addr, SocketAddr},
+};
 
-use crate::command::{FileStructureKind, FormatControl, RepresentationTypeKind, TransferModeKind};
+use crate::command::{
+    ExtendedPassiveKind, FileStructureKind, FormatControl, ProtectionLevel, RepresentationTypeKind,
+    TransferModeKind,
+};
 
 pub trait Serializeable {
     fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
@@ -29,6 +35,33 @@ impl Serializeable for (Ipv4Addr, u16) {
     }
 }
 
+// <d><net-prt><d><net-addr><d><tcp-port><d>, delimited by '|'
+impl Serializeable for SocketAddr {
+    fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let protocol = match self {
+            SocketAddr::V4(_) => 1,
+            SocketAddr::V6(_) => 2,
+        };
+
+        write!(writer, "|{protocol}|{}|{}|", self.ip(), self.port())
+    }
+}
+
+impl Serializeable for ExtendedPassiveKind {
+    fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        match self {
+            ExtendedPassiveKind::Protocol(protocol) => protocol.serialize(writer),
+            ExtendedPassiveKind::All => b"ALL".serialize(writer),
+        }
+    }
+}
+
 impl Serializeable for RepresentationTypeKind {
     fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
     where
@@ -36,20 +69,19 @@ impl Serializeable for RepresentationTypeKind {
     {
         match self {
             RepresentationTypeKind::Ascii(format) => {
-                format.serialize(writer)?;
-                b"A"
+                b"A".serialize(writer)?;
+                format.serialize(writer)
             }
             RepresentationTypeKind::Ebcdic(format) => {
-                format.serialize(writer)?;
-                b"E"
+                b"E".serialize(writer)?;
+                format.serialize(writer)
             }
-            RepresentationTypeKind::Image => b"I",
+            RepresentationTypeKind::Image => b"I".serialize(writer),
             RepresentationTypeKind::LocalByte(size) => {
-                size.serialize(writer)?;
-                b"L"
+                b"L ".serialize(writer)?;
+                size.serialize(writer)
             }
         }
-        .serialize(writer)
     }
 }
 
@@ -90,6 +122,22 @@ impl Serializeable for TransferModeKind {
             TransferModeKind::Stream => b"S",
             TransferModeKind::Block => b"B",
             TransferModeKind::Compressed => b"C",
+            TransferModeKind::Deflate => b"Z",
+        }
+        .serialize(writer)
+    }
+}
+
+impl Serializeable for ProtectionLevel {
+    fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        match self {
+            ProtectionLevel::Clear => b"C",
+            ProtectionLevel::Safe => b"S",
+            ProtectionLevel::Confidential => b"E",
+            ProtectionLevel::Private => b"P",
         }
         .serialize(writer)
     }
@@ -113,6 +161,15 @@ impl Serializeable for i64 {
     }
 }
 
+impl Serializeable for u32 {
+    fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        self.to_string().as_bytes().serialize(writer)
+    }
+}
+
 impl Serializeable for u8 {
     fn serialize<W>(&self, writer: &mut W) -> std::io::Result<()>
     where
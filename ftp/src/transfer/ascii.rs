@@ -0,0 +1,256 @@
+use std::io::{Read, Write};
+
+use super::BUFFER_SIZE;
+
+/// The line ending of text files on the local system.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    #[cfg(windows)]
+    pub const NATIVE: LineEnding = LineEnding::CrLf;
+    #[cfg(not(windows))]
+    pub const NATIVE: LineEnding = LineEnding::Lf;
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        Self::NATIVE
+    }
+}
+
+/// Converts local text written to it into NVT-ASCII, which ends every
+/// line with CRLF, before writing it to the data connection.
+pub struct AsciiEncoder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    previous_was_cr: bool,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<W: Write> AsciiEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            previous_was_cr: false,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes written to the encoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes written to the data connection after conversion.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Flushes the data connection, as nothing is held back.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for AsciiEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        for &c in buf {
+            // A line feed which isn't already part of a CRLF,
+            // possibly split across writes, gets its CR.
+            if c == b'\n' && !self.previous_was_cr {
+                self.buffer.push(b'\r');
+            }
+
+            self.buffer.push(c);
+            self.previous_was_cr = c == b'\r';
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        self.local_bytes += buf.len() as u64;
+        self.network_bytes += self.buffer.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Converts NVT-ASCII read from the data connection into local text.
+pub struct AsciiDecoder<R: Read> {
+    reader: R,
+    line_ending: LineEnding,
+    // Decoded bytes not yet read from the decoder.
+    decoded: Vec<u8>,
+    position: usize,
+    // A CR ending the previous read, whose meaning
+    // depends on the byte following it.
+    pending_cr: bool,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<R: Read> AsciiDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_line_ending(reader, LineEnding::NATIVE)
+    }
+
+    pub fn with_line_ending(reader: R, line_ending: LineEnding) -> Self {
+        Self {
+            reader,
+            line_ending,
+            decoded: Vec::new(),
+            position: 0,
+            pending_cr: false,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes produced by the decoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes read from the data connection before conversion.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for AsciiDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.decoded.len() {
+            self.decoded.clear();
+            self.position = 0;
+
+            let mut input = [0u8; BUFFER_SIZE];
+            let read = self.reader.read(&mut input)?;
+            self.network_bytes += read as u64;
+            if read == 0 {
+                // The connection ended right after a CR.
+                if std::mem::take(&mut self.pending_cr) {
+                    self.decoded.push(b'\r');
+                    continue;
+                }
+
+                return Ok(0);
+            }
+
+            for &c in &input[..read] {
+                if std::mem::take(&mut self.pending_cr) {
+                    if c == b'\n' {
+                        if self.line_ending == LineEnding::CrLf {
+                            self.decoded.push(b'\r');
+                        }
+
+                        self.decoded.push(b'\n');
+                        continue;
+                    }
+
+                    self.decoded.push(b'\r');
+                }
+
+                if c == b'\r' {
+                    self.pending_cr = true;
+                } else {
+                    self.decoded.push(c);
+                }
+            }
+        }
+
+        let amount = buf.len().min(self.decoded.len() - self.position);
+        buf[..amount].copy_from_slice(&self.decoded[self.position..self.position + amount]);
+        self.position += amount;
+        self.local_bytes += amount as u64;
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::{AsciiDecoder, AsciiEncoder, LineEnding};
+
+    // Reads one byte at a time to split every CRLF across reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((&c, rest)) if !buf.is_empty() => {
+                    buf[0] = c;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoder_adds_cr_once() {
+        let mut encoder = AsciiEncoder::new(Vec::new());
+        encoder.write_all(b"a\nb\r").unwrap();
+        encoder.write_all(b"\nc\n").unwrap();
+
+        assert_eq!(encoder.local_bytes(), 7);
+        assert_eq!(encoder.network_bytes(), 9);
+        assert_eq!(encoder.into_inner(), b"a\r\nb\r\nc\r\n");
+    }
+
+    #[test]
+    fn test_decoder_handles_split_crlf() {
+        let mut decoder =
+            AsciiDecoder::with_line_ending(Trickle(b"a\r\nb\rc\r\n\r"), LineEnding::Lf);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, b"a\nb\rc\n\r");
+        assert_eq!(decoder.network_bytes(), 9);
+        assert_eq!(decoder.local_bytes(), 7);
+    }
+
+    #[test]
+    fn test_decoder_keeps_crlf_locally() {
+        let mut decoder = AsciiDecoder::with_line_ending(&b"a\r\nb\r\n"[..], LineEnding::CrLf);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, b"a\r\nb\r\n");
+    }
+}
@@ -0,0 +1,400 @@
+//! Block mode according to RFC 959 section 3.4.2, where data is sent in
+//! blocks preceded by a descriptor byte and a 16 bit count.
+
+use std::io::{Read, Write};
+
+use crate::command::Command;
+
+/// The last block of a record.
+pub(super) const END_OF_RECORD: u8 = 0x80;
+/// The last block of the file.
+pub(super) const END_OF_FILE: u8 = 0x40;
+/// The block's data may contain errors.
+pub(super) const SUSPECTED_ERROR: u8 = 0x20;
+/// The block's data is a restart marker.
+pub(super) const RESTART_MARKER: u8 = 0x10;
+
+const MAX_BLOCK_SIZE: usize = u16::MAX as usize;
+
+/// Sends what is written to it in blocks, collecting writes into blocks
+/// of up to 65535 bytes. The end of the file is sent once finished.
+pub struct BlockEncoder<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+    suspected_error: bool,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<W: Write> BlockEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            block: Vec::new(),
+            suspected_error: false,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes written to the encoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes written to the data connection, including headers.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Ends the current record, sending the data written since the
+    /// last record ended.
+    pub fn end_record(&mut self) -> std::io::Result<()> {
+        self.write_block(END_OF_RECORD)
+    }
+
+    /// Flags the data written from now on as possibly containing errors,
+    /// or stops doing so.
+    pub fn set_suspected_error(&mut self, suspected_error: bool) -> std::io::Result<()> {
+        if self.suspected_error != suspected_error {
+            self.send_data()?;
+            self.suspected_error = suspected_error;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a restart marker after the data written so far. The marker
+    /// must consist of printable characters other than space.
+    pub fn write_restart_marker(&mut self, marker: &[u8]) -> std::io::Result<()> {
+        if marker.is_empty() || marker.len() > MAX_BLOCK_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "restart marker must be between 1 and 65535 bytes",
+            ));
+        }
+
+        self.send_data()?;
+        self.block.extend_from_slice(marker);
+        self.write_block(RESTART_MARKER)
+    }
+
+    /// Sends the amount of local bytes written so far as a restart marker,
+    /// which is what `REST` expects from stream mode servers as well.
+    pub fn mark(&mut self) -> std::io::Result<Vec<u8>> {
+        let marker = self.local_bytes.to_string().into_bytes();
+        self.write_restart_marker(&marker)?;
+        Ok(marker)
+    }
+
+    /// Sends the end of the file.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_block(END_OF_FILE)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn send_data(&mut self) -> std::io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+
+        self.write_block(0)
+    }
+
+    fn write_block(&mut self, descriptor: u8) -> std::io::Result<()> {
+        let descriptor = if self.suspected_error && descriptor & RESTART_MARKER == 0 {
+            descriptor | SUSPECTED_ERROR
+        } else {
+            descriptor
+        };
+
+        let [high, low] = (self.block.len() as u16).to_be_bytes();
+        self.writer.write_all(&[descriptor, high, low])?;
+        self.writer.write_all(&self.block)?;
+        self.network_bytes += 3 + self.block.len() as u64;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BlockEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // A full block is only sent once more data follows, so that
+        // a record ending with it doesn't need an empty block.
+        if self.block.len() == MAX_BLOCK_SIZE && !buf.is_empty() {
+            self.write_block(0)?;
+        }
+
+        let amount = buf.len().min(MAX_BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..amount]);
+        self.local_bytes += amount as u64;
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_data()?;
+        self.writer.flush()
+    }
+}
+
+/// Receives the data of blocks. Reading returns the data of every record
+/// in a row, while `read_record` tells records apart, empty ones included.
+///
+/// Restart markers are collected once the data preceding them was read,
+/// so a marker is only available once everything before it is stored.
+pub struct BlockDecoder<R: Read> {
+    reader: R,
+    descriptor: u8,
+    remaining: usize,
+    end_of_file: bool,
+    restart_markers: Vec<Vec<u8>>,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<R: Read> BlockDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            descriptor: 0,
+            remaining: 0,
+            end_of_file: false,
+            restart_markers: Vec::new(),
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of data bytes read from the decoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes read from the data connection, including headers.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Whether the data read last was flagged as possibly containing errors.
+    pub fn suspected_error(&self) -> bool {
+        self.descriptor & SUSPECTED_ERROR != 0
+    }
+
+    /// Whether the sender ended the file, as opposed to the
+    /// connection closing in the middle of the transfer.
+    pub fn end_of_file(&self) -> bool {
+        self.end_of_file
+    }
+
+    /// The restart markers received so far, in order.
+    pub fn restart_markers(&self) -> &[Vec<u8>] {
+        &self.restart_markers
+    }
+
+    /// The `REST` command resuming the transfer after the last restart marker.
+    pub fn restart_command(&self) -> Option<Command> {
+        self.restart_markers
+            .last()
+            .map(|marker| Command::Restart(marker.clone()))
+    }
+
+    /// Reads the rest of the current record, or `None` once the file ended.
+    /// A connection closing before the end of the file is an `UnexpectedEof`
+    /// error.
+    pub fn read_record(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut record = Vec::new();
+        loop {
+            if self.remaining != 0 {
+                let start = record.len();
+                record.resize(start + self.remaining, 0);
+                self.read_exact(&mut record[start..])?;
+                if self.descriptor & END_OF_RECORD != 0 {
+                    return Ok(Some(record));
+                }
+            }
+
+            if self.end_of_file {
+                // A last record the end of the file implicitly ends.
+                return Ok((!record.is_empty()).then_some(record));
+            }
+
+            if self.next_header()? {
+                return Ok(Some(record));
+            }
+        }
+    }
+
+    // Reads the header of the next block, and the marker of a restart
+    // marker block. Returns whether the block ends a record without data.
+    fn next_header(&mut self) -> std::io::Result<bool> {
+        let mut header = [0u8; 3];
+        self.reader.read_exact(&mut header)?;
+        self.network_bytes += 3;
+
+        let [descriptor, high, low] = header;
+        let count = usize::from(u16::from_be_bytes([high, low]));
+        self.end_of_file = descriptor & END_OF_FILE != 0;
+        if descriptor & RESTART_MARKER != 0 {
+            let mut marker = vec![0u8; count];
+            self.reader.read_exact(&mut marker)?;
+            self.network_bytes += count as u64;
+            self.restart_markers.push(marker);
+            return Ok(false);
+        }
+
+        self.descriptor = descriptor;
+        self.remaining = count;
+        Ok(count == 0 && descriptor & END_OF_RECORD != 0)
+    }
+}
+
+impl<R: Read> Read for BlockDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Empty blocks, such as those ending empty records, carry nothing.
+        while self.remaining == 0 {
+            if self.end_of_file {
+                return Ok(0);
+            }
+
+            self.next_header()?;
+        }
+
+        let amount = buf.len().min(self.remaining);
+        let read = self.reader.read(&mut buf[..amount])?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.remaining -= read;
+        self.local_bytes += read as u64;
+        self.network_bytes += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::command::Command;
+
+    use super::{BlockDecoder, BlockEncoder};
+
+    #[test]
+    fn test_encoder() {
+        let mut encoder = BlockEncoder::new(Vec::new());
+        encoder.write_all(b"ab").unwrap();
+        encoder.end_record().unwrap();
+        encoder.write_all(b"c").unwrap();
+        assert_eq!(encoder.mark().unwrap(), b"3");
+        encoder.set_suspected_error(true).unwrap();
+        encoder.write_all(b"d").unwrap();
+
+        assert_eq!(
+            encoder.finish().unwrap(),
+            b"\x80\x00\x02ab\x00\x00\x01c\x10\x00\x013\x60\x00\x01d"
+        );
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder =
+            BlockDecoder::new(&b"\x80\x00\x02ab\x00\x00\x01c\x10\x00\x013\x60\x00\x01dignored"[..]);
+        let mut buf = [0u8; 16];
+
+        assert_eq!(decoder.read(&mut buf).unwrap(), 2);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert_eq!(decoder.restart_command(), None);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert!(decoder.suspected_error());
+        assert_eq!(
+            decoder.restart_command(),
+            Some(Command::Restart(b"3".to_vec()))
+        );
+        assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+        assert!(decoder.end_of_file());
+    }
+
+    #[test]
+    fn test_decoder_requires_end_of_file() {
+        let mut decoder = BlockDecoder::new(&b"\x00\x00\x02ab"[..]);
+        let mut output = Vec::new();
+
+        assert_eq!(
+            decoder.read_to_end(&mut output).unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(output, b"ab");
+    }
+
+    #[test]
+    fn test_empty_record() {
+        let mut encoder = BlockEncoder::new(Vec::new());
+        encoder.write_all(b"a").unwrap();
+        encoder.flush().unwrap();
+        encoder.end_record().unwrap();
+        encoder.end_record().unwrap();
+        encoder.write_all(b"b").unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoder = BlockDecoder::new(&encoded[..]);
+        assert_eq!(decoder.read_record().unwrap(), Some(b"a".to_vec()));
+        assert_eq!(decoder.read_record().unwrap(), Some(Vec::new()));
+        assert_eq!(decoder.read_record().unwrap(), Some(b"b".to_vec()));
+        assert_eq!(decoder.read_record().unwrap(), None);
+        assert!(decoder.end_of_file());
+
+        // Reading skips the empty blocks ending records.
+        let mut decoder = BlockDecoder::new(&encoded[..]);
+        let mut buf = [0u8; 16];
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf[..1], b"b");
+        assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+        assert!(decoder.end_of_file());
+    }
+
+    #[test]
+    fn test_full_block_record() {
+        let mut encoder = BlockEncoder::new(Vec::new());
+        encoder.write_all(&[7u8; 65535]).unwrap();
+        encoder.end_record().unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(&encoded[..3], b"\x80\xff\xff");
+
+        let mut decoder = BlockDecoder::new(&encoded[..]);
+        assert_eq!(decoder.read_record().unwrap(), Some(vec![7u8; 65535]));
+        assert_eq!(decoder.read_record().unwrap(), None);
+        assert!(decoder.end_of_file());
+    }
+
+    #[test]
+    fn test_large_writes_are_split() {
+        let mut encoder = BlockEncoder::new(Vec::new());
+        encoder.write_all(&vec![7u8; 70_000]).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoder = BlockDecoder::new(&encoded[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, vec![7u8; 70_000]);
+        assert_eq!(decoder.network_bytes(), 70_000 + 2 * 3);
+    }
+}
@@ -0,0 +1,505 @@
+//! Compressed mode according to RFC 959 section 3.4.3, where data is sent
+//! as runs of literal, replicated and filler bytes, with escape sequences
+//! carrying the descriptor codes of block mode.
+
+use std::io::{Read, Write};
+
+use crate::command::{Command, RepresentationTypeKind};
+
+use super::{
+    block::{END_OF_FILE, END_OF_RECORD, RESTART_MARKER, SUSPECTED_ERROR},
+    BUFFER_SIZE,
+};
+
+const ESCAPE: u8 = 0x00;
+const REPLICATED: u8 = 0x80;
+const FILLER: u8 = 0xC0;
+
+const MAX_LITERAL: usize = 0x7F;
+const MAX_RUN: usize = 0x3F;
+
+// The byte filler runs stand for, which is a space
+// for text and zero for anything else.
+fn filler_byte(kind: RepresentationTypeKind) -> u8 {
+    match kind {
+        RepresentationTypeKind::Ascii(_) => b' ',
+        RepresentationTypeKind::Ebcdic(_) => 0x40,
+        RepresentationTypeKind::Image | RepresentationTypeKind::LocalByte(_) => 0x00,
+    }
+}
+
+/// Compresses what is written to it. The end of the file is sent once finished.
+///
+/// Writes are collected before compressing them, as the next write
+/// may continue the last run.
+pub struct CompressedEncoder<W: Write> {
+    writer: W,
+    filler: u8,
+    pending: Vec<u8>,
+    buffer: Vec<u8>,
+    suspected_error: bool,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<W: Write> CompressedEncoder<W> {
+    /// Creates an encoder for data already in the representation type `kind`.
+    pub fn new(writer: W, kind: RepresentationTypeKind) -> Self {
+        Self {
+            writer,
+            filler: filler_byte(kind),
+            pending: Vec::new(),
+            buffer: Vec::new(),
+            suspected_error: false,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes written to the encoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes written to the data connection after compression.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Ends the current record.
+    pub fn end_record(&mut self) -> std::io::Result<()> {
+        self.write_escape(END_OF_RECORD, &[])
+    }
+
+    /// Flags the data written from now on as possibly containing errors,
+    /// or stops doing so.
+    pub fn set_suspected_error(&mut self, suspected_error: bool) -> std::io::Result<()> {
+        if self.suspected_error != suspected_error {
+            self.suspected_error = suspected_error;
+            self.write_escape(0, &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a restart marker after the data written so far. The marker
+    /// must consist of printable characters other than space.
+    pub fn write_restart_marker(&mut self, marker: &[u8]) -> std::io::Result<()> {
+        if marker.is_empty() || marker.len() > MAX_LITERAL {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "restart marker must be between 1 and 127 bytes",
+            ));
+        }
+
+        self.write_escape(RESTART_MARKER, marker)
+    }
+
+    /// Sends the amount of local bytes written so far as a restart marker.
+    pub fn mark(&mut self) -> std::io::Result<Vec<u8>> {
+        let marker = self.local_bytes.to_string().into_bytes();
+        self.write_restart_marker(&marker)?;
+        Ok(marker)
+    }
+
+    /// Sends the data held back and the end of the file.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_escape(END_OF_FILE, &[])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    // Sends the data held back followed by an escape sequence,
+    // and the data the descriptor applies to.
+    fn write_escape(&mut self, descriptor: u8, data: &[u8]) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.compress(&pending);
+
+        let suspected_error = if self.suspected_error {
+            SUSPECTED_ERROR
+        } else {
+            0
+        };
+
+        self.buffer
+            .extend_from_slice(&[ESCAPE, descriptor | suspected_error]);
+        self.push_literal(data);
+        self.send()
+    }
+
+    fn send(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.network_bytes += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn compress(&mut self, data: &[u8]) {
+        let mut literal = 0;
+        let mut index = 0;
+        while index < data.len() {
+            let c = data[index];
+            let run = data[index..]
+                .iter()
+                .take_while(|&&other| other == c)
+                .count();
+
+            // Runs shorter than their encoding are sent literally.
+            let worth = if c == self.filler { run >= 2 } else { run >= 3 };
+            if worth {
+                self.push_literal(&data[literal..index]);
+                self.push_run(c, run);
+                literal = index + run;
+            }
+
+            index += run;
+        }
+
+        self.push_literal(&data[literal..]);
+    }
+
+    fn push_literal(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_LITERAL) {
+            self.buffer.push(chunk.len() as u8);
+            self.buffer.extend_from_slice(chunk);
+        }
+    }
+
+    fn push_run(&mut self, c: u8, mut run: usize) {
+        while run > 0 {
+            let amount = run.min(MAX_RUN);
+            if c == self.filler {
+                self.buffer.push(FILLER | amount as u8);
+            } else {
+                self.buffer
+                    .extend_from_slice(&[REPLICATED | amount as u8, c]);
+            }
+
+            run -= amount;
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.local_bytes += buf.len() as u64;
+
+        // Compress once enough was written, holding back the last run
+        // unless that's all there is.
+        if self.pending.len() >= BUFFER_SIZE {
+            let last = self.pending.last().copied();
+            let run = self
+                .pending
+                .iter()
+                .rev()
+                .take_while(|&&c| Some(c) == last)
+                .count();
+            let held_back = if run < self.pending.len() { run } else { 0 };
+            let pending = self.pending.split_off(self.pending.len() - held_back);
+            let complete = std::mem::replace(&mut self.pending, pending);
+            self.compress(&complete);
+            self.send()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.compress(&pending);
+        self.send()?;
+        self.writer.flush()
+    }
+}
+
+/// Decompresses data read from the data connection.
+///
+/// A read never returns data of more than one record, and restart markers
+/// are collected once the data preceding them was read, as in block mode.
+pub struct CompressedDecoder<R: Read> {
+    reader: R,
+    filler: u8,
+    decoded: Vec<u8>,
+    position: usize,
+    // Whether the decoded data ends a record.
+    end_of_record: bool,
+    end_of_file: bool,
+    suspected_error: bool,
+    // A restart marker following the decoded data.
+    pending_marker: Option<Vec<u8>>,
+    restart_markers: Vec<Vec<u8>>,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<R: Read> CompressedDecoder<R> {
+    /// Creates a decoder for data in the representation type `kind`.
+    pub fn new(reader: R, kind: RepresentationTypeKind) -> Self {
+        Self {
+            reader,
+            filler: filler_byte(kind),
+            decoded: Vec::new(),
+            position: 0,
+            end_of_record: false,
+            end_of_file: false,
+            suspected_error: false,
+            pending_marker: None,
+            restart_markers: Vec::new(),
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of decompressed bytes read from the decoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes read from the data connection before decompression.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Whether the data read last was flagged as possibly containing errors.
+    pub fn suspected_error(&self) -> bool {
+        self.suspected_error
+    }
+
+    /// Whether the sender ended the file, as opposed to the
+    /// connection closing in the middle of the transfer.
+    pub fn end_of_file(&self) -> bool {
+        self.end_of_file
+    }
+
+    /// The restart markers received so far, in order.
+    pub fn restart_markers(&self) -> &[Vec<u8>] {
+        &self.restart_markers
+    }
+
+    /// The `REST` command resuming the transfer after the last restart marker.
+    pub fn restart_command(&self) -> Option<Command> {
+        self.restart_markers
+            .last()
+            .map(|marker| Command::Restart(marker.clone()))
+    }
+
+    /// Reads the rest of the current record, or `None` once the file ended.
+    /// A connection closing before the end of the file is an `UnexpectedEof`
+    /// error.
+    pub fn read_record(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut record = Vec::new();
+        loop {
+            record.extend_from_slice(&self.decoded[self.position..]);
+            self.local_bytes += (self.decoded.len() - self.position) as u64;
+            self.position = self.decoded.len();
+            if self.end_of_record {
+                self.end_of_record = false;
+                return Ok(Some(record));
+            }
+
+            if self.end_of_file {
+                self.restart_markers.extend(self.pending_marker.take());
+                // A last record the end of the file implicitly ends.
+                return Ok((!record.is_empty()).then_some(record));
+            }
+
+            self.fill()?;
+        }
+    }
+
+    fn read_bytes(&mut self, amount: usize) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; amount];
+        self.reader.read_exact(&mut bytes)?;
+        self.network_bytes += amount as u64;
+        Ok(bytes)
+    }
+
+    fn read_byte(&mut self) -> std::io::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    // Decodes until the buffer is full, or a record or the file ends,
+    // or a restart marker follows.
+    fn fill(&mut self) -> std::io::Result<()> {
+        self.decoded.clear();
+        self.position = 0;
+        self.end_of_record = false;
+        self.restart_markers.extend(self.pending_marker.take());
+
+        while !self.end_of_file && self.decoded.len() < BUFFER_SIZE {
+            let header = self.read_byte()?;
+            let amount = usize::from(header & MAX_RUN as u8);
+            match header {
+                ESCAPE => {
+                    let descriptor = self.read_byte()?;
+                    self.suspected_error = descriptor & SUSPECTED_ERROR != 0;
+                    self.end_of_record = descriptor & END_OF_RECORD != 0;
+                    self.end_of_file = descriptor & END_OF_FILE != 0;
+                    if descriptor & RESTART_MARKER != 0 {
+                        let length = self.read_byte()?;
+                        if length & REPLICATED != 0 {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                "restart marker must be sent literally",
+                            ));
+                        }
+
+                        self.pending_marker = Some(self.read_bytes(usize::from(length))?);
+                    }
+
+                    if descriptor & (END_OF_RECORD | RESTART_MARKER) != 0 {
+                        break;
+                    }
+                }
+                header if header & REPLICATED == 0 => {
+                    let literal = self.read_bytes(usize::from(header))?;
+                    self.decoded.extend_from_slice(&literal);
+                }
+                header if header & FILLER == REPLICATED => {
+                    let c = self.read_byte()?;
+                    self.decoded.resize(self.decoded.len() + amount, c);
+                }
+                _ => self
+                    .decoded
+                    .resize(self.decoded.len() + amount, self.filler),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for CompressedDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Empty records carry nothing, so only the end of the file reads as 0.
+        while self.position == self.decoded.len() {
+            if self.end_of_file {
+                self.restart_markers.extend(self.pending_marker.take());
+                return Ok(0);
+            }
+
+            self.fill()?;
+        }
+
+        let amount = buf.len().min(self.decoded.len() - self.position);
+        buf[..amount].copy_from_slice(&self.decoded[self.position..self.position + amount]);
+        self.position += amount;
+        self.local_bytes += amount as u64;
+        if self.position == self.decoded.len() {
+            // The end of the record was read along.
+            self.end_of_record = false;
+        }
+
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::command::{Command, RepresentationTypeKind};
+
+    use super::{CompressedDecoder, CompressedEncoder};
+
+    const KIND: RepresentationTypeKind = RepresentationTypeKind::Ascii(None);
+
+    #[test]
+    fn test_encoder() {
+        let mut encoder = CompressedEncoder::new(Vec::new(), KIND);
+        encoder.write_all(b"xaa").unwrap();
+        encoder.write_all(b"aab  ").unwrap();
+        encoder.write_all(b" ").unwrap();
+
+        assert_eq!(
+            encoder.finish().unwrap(),
+            b"\x01x\x84a\x01b\xc3\x00\x40".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..1000u32)
+            .flat_map(|i| vec![(i % 7) as u8 + b' '; (i % 200) as usize])
+            .collect();
+
+        let mut encoder = CompressedEncoder::new(Vec::new(), KIND);
+        encoder.write_all(&data[..5000]).unwrap();
+        encoder.end_record().unwrap();
+        encoder.write_all(&data[5000..]).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert!(encoded.len() < data.len() / 10);
+
+        let mut decoder = CompressedDecoder::new(&encoded[..], KIND);
+        let mut first = vec![0u8; 4000];
+        decoder.read_exact(&mut first).unwrap();
+        assert_eq!(decoder.read_record().unwrap().unwrap(), data[4000..5000]);
+        assert_eq!(decoder.read_record().unwrap().unwrap(), data[5000..]);
+        assert_eq!(decoder.read_record().unwrap(), None);
+        assert!(decoder.end_of_file());
+
+        let mut decoder = CompressedDecoder::new(&encoded[..], KIND);
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_empty_record() {
+        let mut encoder = CompressedEncoder::new(Vec::new(), KIND);
+        encoder.write_all(b"a").unwrap();
+        encoder.end_record().unwrap();
+        encoder.end_record().unwrap();
+        encoder.write_all(b"b").unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoder = CompressedDecoder::new(&encoded[..], KIND);
+        assert_eq!(decoder.read_record().unwrap(), Some(b"a".to_vec()));
+        assert_eq!(decoder.read_record().unwrap(), Some(Vec::new()));
+        assert_eq!(decoder.read_record().unwrap(), Some(b"b".to_vec()));
+        assert_eq!(decoder.read_record().unwrap(), None);
+
+        let mut decoder = CompressedDecoder::new(&encoded[..], KIND);
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"ab");
+    }
+
+    #[test]
+    fn test_restart_marker() {
+        let mut encoder = CompressedEncoder::new(Vec::new(), KIND);
+        encoder.write_all(b"abc").unwrap();
+        encoder.mark().unwrap();
+        encoder.set_suspected_error(true).unwrap();
+        encoder.write_all(b"d").unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(
+            encoded,
+            b"\x03abc\x00\x10\x013\x00\x20\x01d\x00\x60".to_vec()
+        );
+
+        let mut decoder = CompressedDecoder::new(&encoded[..], KIND);
+        let mut buf = [0u8; 16];
+        assert_eq!(decoder.read(&mut buf).unwrap(), 3);
+        assert_eq!(decoder.restart_command(), None);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert!(decoder.suspected_error());
+        assert_eq!(
+            decoder.restart_command(),
+            Some(Command::Restart(b"3".to_vec()))
+        );
+    }
+}
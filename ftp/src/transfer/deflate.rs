@@ -0,0 +1,175 @@
+//! Deflate mode (`MODE Z`), where the data connection carries a single
+//! zlib stream of the whole file.
+
+use std::io::{Read, Write};
+
+use crate::command::Command;
+
+// Compression level used unless `OPTS MODE Z LEVEL` sets another.
+pub(super) const DEFAULT_LEVEL: u32 = 6;
+const MAX_LEVEL: u32 = 9;
+
+/// Compresses what is written to it. The zlib stream is ended once finished.
+pub struct DeflateEncoder<W: Write> {
+    encoder: flate2::write::ZlibEncoder<W>,
+}
+
+impl<W: Write> DeflateEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_level(writer, DEFAULT_LEVEL)
+    }
+
+    /// Creates an encoder of a compression level from 0 to 9.
+    pub fn with_level(writer: W, level: u32) -> Self {
+        Self {
+            encoder: flate2::write::ZlibEncoder::new(
+                writer,
+                flate2::Compression::new(level.min(MAX_LEVEL)),
+            ),
+        }
+    }
+
+    /// Amount of local bytes written to the encoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.encoder.total_in()
+    }
+
+    /// Amount of bytes written to the data connection after compression.
+    pub fn network_bytes(&self) -> u64 {
+        self.encoder.total_out()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.encoder.get_ref()
+    }
+
+    /// Writes the rest of the zlib stream.
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut writer = self.encoder.finish()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for DeflateEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Decompresses the zlib stream read from the data connection.
+pub struct DeflateDecoder<R: Read> {
+    decoder: flate2::read::ZlibDecoder<R>,
+}
+
+impl<R: Read> DeflateDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            decoder: flate2::read::ZlibDecoder::new(reader),
+        }
+    }
+
+    /// Amount of decompressed bytes read from the decoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.decoder.total_out()
+    }
+
+    /// Amount of bytes read from the data connection before decompression.
+    pub fn network_bytes(&self) -> u64 {
+        self.decoder.total_in()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.decoder.get_ref()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.decoder.into_inner()
+    }
+}
+
+impl<R: Read> Read for DeflateDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+/// The `OPTS MODE Z LEVEL <level>` command setting the compression level.
+pub fn level_option(level: u32) -> Command {
+    Command::Options(
+        b"MODE".to_vec(),
+        Some(format!("Z LEVEL {level}").into_bytes()),
+    )
+}
+
+/// The compression level set by an `OPTS MODE Z LEVEL <level>` command,
+/// or `None` for any other command.
+pub fn parse_level_option(command: &Command) -> Option<u32> {
+    let Command::Options(name, Some(options)) = command else {
+        return None;
+    };
+    if !name.eq_ignore_ascii_case(b"MODE") {
+        return None;
+    }
+
+    let mut words = options
+        .split(|&c| c == b' ')
+        .filter(|word| !word.is_empty());
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some(mode), Some(option), Some(level), None)
+            if mode.eq_ignore_ascii_case(b"Z") && option.eq_ignore_ascii_case(b"LEVEL") =>
+        {
+            std::str::from_utf8(level)
+                .ok()?
+                .parse()
+                .ok()
+                .filter(|&level| level <= MAX_LEVEL)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::command::Command;
+
+    use super::{level_option, parse_level_option, DeflateDecoder, DeflateEncoder};
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"a line of a large text export\r\n".repeat(1000);
+        let mut encoder = DeflateEncoder::with_level(Vec::new(), 9);
+        encoder.write_all(&data).unwrap();
+        assert_eq!(encoder.local_bytes(), data.len() as u64);
+        let encoded = encoder.finish().unwrap();
+        assert!(encoded.len() < data.len() / 50);
+
+        let mut decoder = DeflateDecoder::new(&encoded[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+        assert_eq!(decoder.network_bytes(), encoded.len() as u64);
+    }
+
+    #[test]
+    fn test_level_option() {
+        assert_eq!(parse_level_option(&level_option(3)), Some(3));
+        assert_eq!(
+            parse_level_option(&Command::Options(
+                b"mode".to_vec(),
+                Some(b"z level 12".to_vec())
+            )),
+            None
+        );
+        assert_eq!(
+            parse_level_option(&Command::Options(b"UTF8".to_vec(), Some(b"ON".to_vec()))),
+            None
+        );
+    }
+}
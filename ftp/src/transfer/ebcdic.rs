@@ -0,0 +1,394 @@
+use std::io::{Read, Write};
+
+use super::{LineEnding, BUFFER_SIZE};
+
+// End of line in EBCDIC according to RFC 959 section 3.1.1.2.
+const NL: u8 = 0x15;
+// Replaces characters the code page can't represent.
+const SUB: u8 = 0x3F;
+
+/// A table translating the 256 EBCDIC bytes of a code page to Unicode.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CodePage {
+    table: [char; 256],
+}
+
+impl CodePage {
+    /// The code page of US and Canadian mainframes.
+    pub const CP037: CodePage = CodePage::new(CP037_TABLE);
+    /// The Latin-1 open systems code page, used by z/OS UNIX.
+    pub const CP1047: CodePage = CodePage::new(CP1047_TABLE);
+
+    /// Creates a code page from the character each EBCDIC byte stands for.
+    pub const fn new(table: [char; 256]) -> Self {
+        Self { table }
+    }
+
+    pub fn decode(&self, byte: u8) -> char {
+        self.table[usize::from(byte)]
+    }
+
+    pub fn encode(&self, c: char) -> Option<u8> {
+        self.table
+            .iter()
+            .position(|&entry| entry == c)
+            .map(|byte| byte as u8)
+    }
+}
+
+/// Converts local UTF-8 text written to it into EBCDIC before writing
+/// it to the data connection. Lines end with the EBCDIC NL character
+/// and characters missing from the code page are replaced with SUB.
+///
+/// A CR is held back until the byte following it is written, so
+/// `finish` must be called once all of the text has been written.
+pub struct EbcdicEncoder<W: Write> {
+    writer: W,
+    code_page: CodePage,
+    // EBCDIC bytes of the first 256 code points, to spare a search.
+    latin: [Option<u8>; 256],
+    buffer: Vec<u8>,
+    // The start of a UTF-8 sequence split across writes.
+    partial: Vec<u8>,
+    pending_cr: bool,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<W: Write> EbcdicEncoder<W> {
+    pub fn new(writer: W, code_page: CodePage) -> Self {
+        let mut latin = [None; 256];
+        for byte in (0..=255u8).rev() {
+            let c = code_page.decode(byte) as usize;
+            if c < 256 {
+                latin[c] = Some(byte);
+            }
+        }
+
+        Self {
+            writer,
+            code_page,
+            latin,
+            buffer: Vec::new(),
+            partial: Vec::new(),
+            pending_cr: false,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes written to the encoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes written to the data connection after conversion.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Writes anything held back waiting for more input, like a trailing
+    /// CR or an incomplete UTF-8 sequence, and returns the writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.buffer.clear();
+        if std::mem::take(&mut self.pending_cr) {
+            self.push_char('\r');
+        }
+
+        if !std::mem::take(&mut self.partial).is_empty() {
+            self.buffer.push(SUB);
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        self.network_bytes += self.buffer.len() as u64;
+        Ok(self.writer)
+    }
+
+    fn push_char(&mut self, c: char) {
+        let byte = match c as usize {
+            code if code < 256 => self.latin[code],
+            _ => self.code_page.encode(c),
+        };
+
+        self.buffer.push(byte.unwrap_or(SUB));
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if std::mem::take(&mut self.pending_cr) && c != '\n' {
+                self.push_char('\r');
+            }
+
+            match c {
+                '\r' => self.pending_cr = true,
+                '\n' => self.buffer.push(NL),
+                c => self.push_char(c),
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for EbcdicEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+
+        let mut input = std::mem::take(&mut self.partial);
+        input.extend_from_slice(buf);
+        let mut remaining = &input[..];
+        loop {
+            match std::str::from_utf8(remaining) {
+                Ok(text) => {
+                    self.push_text(text);
+                    break;
+                }
+                Err(error) => {
+                    let (valid, invalid) = remaining.split_at(error.valid_up_to());
+                    // Valid by the definition of `valid_up_to`.
+                    self.push_text(std::str::from_utf8(valid).unwrap_or_default());
+                    match error.error_len() {
+                        Some(length) => {
+                            self.push_text("\u{FFFD}");
+                            remaining = &invalid[length..];
+                        }
+                        None => {
+                            self.partial = invalid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        self.local_bytes += buf.len() as u64;
+        self.network_bytes += self.buffer.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Converts EBCDIC read from the data connection into local UTF-8 text,
+/// ending lines with the local line ending instead of NL.
+pub struct EbcdicDecoder<R: Read> {
+    reader: R,
+    code_page: CodePage,
+    line_ending: LineEnding,
+    decoded: Vec<u8>,
+    position: usize,
+    local_bytes: u64,
+    network_bytes: u64,
+}
+
+impl<R: Read> EbcdicDecoder<R> {
+    pub fn new(reader: R, code_page: CodePage) -> Self {
+        Self::with_line_ending(reader, code_page, LineEnding::NATIVE)
+    }
+
+    pub fn with_line_ending(reader: R, code_page: CodePage, line_ending: LineEnding) -> Self {
+        Self {
+            reader,
+            code_page,
+            line_ending,
+            decoded: Vec::new(),
+            position: 0,
+            local_bytes: 0,
+            network_bytes: 0,
+        }
+    }
+
+    /// Amount of local bytes produced by the decoder.
+    pub fn local_bytes(&self) -> u64 {
+        self.local_bytes
+    }
+
+    /// Amount of bytes read from the data connection before conversion.
+    pub fn network_bytes(&self) -> u64 {
+        self.network_bytes
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for EbcdicDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.decoded.len() {
+            self.decoded.clear();
+            self.position = 0;
+
+            let mut input = [0u8; BUFFER_SIZE];
+            let read = self.reader.read(&mut input)?;
+            if read == 0 {
+                return Ok(0);
+            }
+
+            self.network_bytes += read as u64;
+            for &byte in &input[..read] {
+                if byte == NL {
+                    if self.line_ending == LineEnding::CrLf {
+                        self.decoded.push(b'\r');
+                    }
+
+                    self.decoded.push(b'\n');
+                    continue;
+                }
+
+                let mut utf8 = [0u8; 4];
+                self.decoded.extend_from_slice(
+                    self.code_page
+                        .decode(byte)
+                        .encode_utf8(&mut utf8)
+                        .as_bytes(),
+                );
+            }
+        }
+
+        let amount = buf.len().min(self.decoded.len() - self.position);
+        buf[..amount].copy_from_slice(&self.decoded[self.position..self.position + amount]);
+        self.position += amount;
+        self.local_bytes += amount as u64;
+        Ok(amount)
+    }
+}
+
+const CP037_TABLE: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9c}', '\u{09}', '\u{86}', '\u{7f}', // 0x00
+    '\u{97}', '\u{8d}', '\u{8e}', '\u{0b}', '\u{0c}', '\u{0d}', '\u{0e}', '\u{0f}', // 0x08
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9d}', '\u{85}', '\u{08}', '\u{87}', // 0x10
+    '\u{18}', '\u{19}', '\u{92}', '\u{8f}', '\u{1c}', '\u{1d}', '\u{1e}', '\u{1f}', // 0x18
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0a}', '\u{17}', '\u{1b}', // 0x20
+    '\u{88}', '\u{89}', '\u{8a}', '\u{8b}', '\u{8c}', '\u{05}', '\u{06}', '\u{07}', // 0x28
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}', // 0x30
+    '\u{98}', '\u{99}', '\u{9a}', '\u{9b}', '\u{14}', '\u{15}', '\u{9e}', '\u{1a}', // 0x38
+    '\u{20}', '\u{a0}', '\u{e2}', '\u{e4}', '\u{e0}', '\u{e1}', '\u{e3}', '\u{e5}', // 0x40
+    '\u{e7}', '\u{f1}', '\u{a2}', '\u{2e}', '\u{3c}', '\u{28}', '\u{2b}', '\u{7c}', // 0x48
+    '\u{26}', '\u{e9}', '\u{ea}', '\u{eb}', '\u{e8}', '\u{ed}', '\u{ee}', '\u{ef}', // 0x50
+    '\u{ec}', '\u{df}', '\u{21}', '\u{24}', '\u{2a}', '\u{29}', '\u{3b}', '\u{ac}', // 0x58
+    '\u{2d}', '\u{2f}', '\u{c2}', '\u{c4}', '\u{c0}', '\u{c1}', '\u{c3}', '\u{c5}', // 0x60
+    '\u{c7}', '\u{d1}', '\u{a6}', '\u{2c}', '\u{25}', '\u{5f}', '\u{3e}', '\u{3f}', // 0x68
+    '\u{f8}', '\u{c9}', '\u{ca}', '\u{cb}', '\u{c8}', '\u{cd}', '\u{ce}', '\u{cf}', // 0x70
+    '\u{cc}', '\u{60}', '\u{3a}', '\u{23}', '\u{40}', '\u{27}', '\u{3d}', '\u{22}', // 0x78
+    '\u{d8}', '\u{61}', '\u{62}', '\u{63}', '\u{64}', '\u{65}', '\u{66}', '\u{67}', // 0x80
+    '\u{68}', '\u{69}', '\u{ab}', '\u{bb}', '\u{f0}', '\u{fd}', '\u{fe}', '\u{b1}', // 0x88
+    '\u{b0}', '\u{6a}', '\u{6b}', '\u{6c}', '\u{6d}', '\u{6e}', '\u{6f}', '\u{70}', // 0x90
+    '\u{71}', '\u{72}', '\u{aa}', '\u{ba}', '\u{e6}', '\u{b8}', '\u{c6}', '\u{a4}', // 0x98
+    '\u{b5}', '\u{7e}', '\u{73}', '\u{74}', '\u{75}', '\u{76}', '\u{77}', '\u{78}', // 0xA0
+    '\u{79}', '\u{7a}', '\u{a1}', '\u{bf}', '\u{d0}', '\u{dd}', '\u{de}', '\u{ae}', // 0xA8
+    '\u{5e}', '\u{a3}', '\u{a5}', '\u{b7}', '\u{a9}', '\u{a7}', '\u{b6}', '\u{bc}', // 0xB0
+    '\u{bd}', '\u{be}', '\u{5b}', '\u{5d}', '\u{af}', '\u{a8}', '\u{b4}', '\u{d7}', // 0xB8
+    '\u{7b}', '\u{41}', '\u{42}', '\u{43}', '\u{44}', '\u{45}', '\u{46}', '\u{47}', // 0xC0
+    '\u{48}', '\u{49}', '\u{ad}', '\u{f4}', '\u{f6}', '\u{f2}', '\u{f3}', '\u{f5}', // 0xC8
+    '\u{7d}', '\u{4a}', '\u{4b}', '\u{4c}', '\u{4d}', '\u{4e}', '\u{4f}', '\u{50}', // 0xD0
+    '\u{51}', '\u{52}', '\u{b9}', '\u{fb}', '\u{fc}', '\u{f9}', '\u{fa}', '\u{ff}', // 0xD8
+    '\u{5c}', '\u{f7}', '\u{53}', '\u{54}', '\u{55}', '\u{56}', '\u{57}', '\u{58}', // 0xE0
+    '\u{59}', '\u{5a}', '\u{b2}', '\u{d4}', '\u{d6}', '\u{d2}', '\u{d3}', '\u{d5}', // 0xE8
+    '\u{30}', '\u{31}', '\u{32}', '\u{33}', '\u{34}', '\u{35}', '\u{36}', '\u{37}', // 0xF0
+    '\u{38}', '\u{39}', '\u{b3}', '\u{db}', '\u{dc}', '\u{d9}', '\u{da}', '\u{9f}', // 0xF8
+];
+
+const CP1047_TABLE: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9c}', '\u{09}', '\u{86}', '\u{7f}', // 0x00
+    '\u{97}', '\u{8d}', '\u{8e}', '\u{0b}', '\u{0c}', '\u{0d}', '\u{0e}', '\u{0f}', // 0x08
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9d}', '\u{85}', '\u{08}', '\u{87}', // 0x10
+    '\u{18}', '\u{19}', '\u{92}', '\u{8f}', '\u{1c}', '\u{1d}', '\u{1e}', '\u{1f}', // 0x18
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0a}', '\u{17}', '\u{1b}', // 0x20
+    '\u{88}', '\u{89}', '\u{8a}', '\u{8b}', '\u{8c}', '\u{05}', '\u{06}', '\u{07}', // 0x28
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}', // 0x30
+    '\u{98}', '\u{99}', '\u{9a}', '\u{9b}', '\u{14}', '\u{15}', '\u{9e}', '\u{1a}', // 0x38
+    '\u{20}', '\u{a0}', '\u{e2}', '\u{e4}', '\u{e0}', '\u{e1}', '\u{e3}', '\u{e5}', // 0x40
+    '\u{e7}', '\u{f1}', '\u{a2}', '\u{2e}', '\u{3c}', '\u{28}', '\u{2b}', '\u{7c}', // 0x48
+    '\u{26}', '\u{e9}', '\u{ea}', '\u{eb}', '\u{e8}', '\u{ed}', '\u{ee}', '\u{ef}', // 0x50
+    '\u{ec}', '\u{df}', '\u{21}', '\u{24}', '\u{2a}', '\u{29}', '\u{3b}', '\u{5e}', // 0x58
+    '\u{2d}', '\u{2f}', '\u{c2}', '\u{c4}', '\u{c0}', '\u{c1}', '\u{c3}', '\u{c5}', // 0x60
+    '\u{c7}', '\u{d1}', '\u{a6}', '\u{2c}', '\u{25}', '\u{5f}', '\u{3e}', '\u{3f}', // 0x68
+    '\u{f8}', '\u{c9}', '\u{ca}', '\u{cb}', '\u{c8}', '\u{cd}', '\u{ce}', '\u{cf}', // 0x70
+    '\u{cc}', '\u{60}', '\u{3a}', '\u{23}', '\u{40}', '\u{27}', '\u{3d}', '\u{22}', // 0x78
+    '\u{d8}', '\u{61}', '\u{62}', '\u{63}', '\u{64}', '\u{65}', '\u{66}', '\u{67}', // 0x80
+    '\u{68}', '\u{69}', '\u{ab}', '\u{bb}', '\u{f0}', '\u{fd}', '\u{fe}', '\u{b1}', // 0x88
+    '\u{b0}', '\u{6a}', '\u{6b}', '\u{6c}', '\u{6d}', '\u{6e}', '\u{6f}', '\u{70}', // 0x90
+    '\u{71}', '\u{72}', '\u{aa}', '\u{ba}', '\u{e6}', '\u{b8}', '\u{c6}', '\u{a4}', // 0x98
+    '\u{b5}', '\u{7e}', '\u{73}', '\u{74}', '\u{75}', '\u{76}', '\u{77}', '\u{78}', // 0xA0
+    '\u{79}', '\u{7a}', '\u{a1}', '\u{bf}', '\u{d0}', '\u{5b}', '\u{de}', '\u{ae}', // 0xA8
+    '\u{ac}', '\u{a3}', '\u{a5}', '\u{b7}', '\u{a9}', '\u{a7}', '\u{b6}', '\u{bc}', // 0xB0
+    '\u{bd}', '\u{be}', '\u{dd}', '\u{a8}', '\u{af}', '\u{5d}', '\u{b4}', '\u{d7}', // 0xB8
+    '\u{7b}', '\u{41}', '\u{42}', '\u{43}', '\u{44}', '\u{45}', '\u{46}', '\u{47}', // 0xC0
+    '\u{48}', '\u{49}', '\u{ad}', '\u{f4}', '\u{f6}', '\u{f2}', '\u{f3}', '\u{f5}', // 0xC8
+    '\u{7d}', '\u{4a}', '\u{4b}', '\u{4c}', '\u{4d}', '\u{4e}', '\u{4f}', '\u{50}', // 0xD0
+    '\u{51}', '\u{52}', '\u{b9}', '\u{fb}', '\u{fc}', '\u{f9}', '\u{fa}', '\u{ff}', // 0xD8
+    '\u{5c}', '\u{f7}', '\u{53}', '\u{54}', '\u{55}', '\u{56}', '\u{57}', '\u{58}', // 0xE0
+    '\u{59}', '\u{5a}', '\u{b2}', '\u{d4}', '\u{d6}', '\u{d2}', '\u{d3}', '\u{d5}', // 0xE8
+    '\u{30}', '\u{31}', '\u{32}', '\u{33}', '\u{34}', '\u{35}', '\u{36}', '\u{37}', // 0xF0
+    '\u{38}', '\u{39}', '\u{b3}', '\u{db}', '\u{dc}', '\u{d9}', '\u{da}', '\u{9f}', // 0xF8
+];
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::{CodePage, EbcdicDecoder, EbcdicEncoder};
+    use crate::transfer::LineEnding;
+
+    #[test]
+    fn test_encode_lines() {
+        let mut encoder = EbcdicEncoder::new(Vec::new(), CodePage::CP037);
+        encoder.write_all(b"AB\r").unwrap();
+        encoder.write_all(b"\nab\n").unwrap();
+
+        assert_eq!(
+            encoder.finish().unwrap(),
+            [0xC1, 0xC2, 0x15, 0x81, 0x82, 0x15]
+        );
+    }
+
+    #[test]
+    fn test_encode_split_utf8_and_missing_characters() {
+        let mut encoder = EbcdicEncoder::new(Vec::new(), CodePage::CP037);
+        let text = "é€".as_bytes();
+        encoder.write_all(&text[..1]).unwrap();
+        encoder.write_all(&text[1..]).unwrap();
+
+        assert_eq!(encoder.finish().unwrap(), [0x51, 0x3F]);
+    }
+
+    #[test]
+    fn test_code_pages_differ_in_brackets() {
+        assert_eq!(CodePage::CP037.encode('['), Some(0xBA));
+        assert_eq!(CodePage::CP1047.encode('['), Some(0xAD));
+        assert_eq!(CodePage::CP1047.decode(0x5F), '^');
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut decoder = EbcdicDecoder::with_line_ending(
+            &[0xC8, 0x89, 0x15, 0x51, 0x15][..],
+            CodePage::CP1047,
+            LineEnding::CrLf,
+        );
+        let mut output = String::new();
+        decoder.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, "Hi\r\né\r\n");
+        assert_eq!(decoder.network_bytes(), 5);
+        assert_eq!(decoder.local_bytes(), 8);
+    }
+}
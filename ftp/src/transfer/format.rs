@@ -0,0 +1,410 @@
+//! Vertical format controls of print files, converted to and from plain
+//! text where lines end with LF, pages start with FF and a bare CR
+//! returns to the start of the line to overprint it.
+
+use std::io::{Read, Write};
+
+use super::BUFFER_SIZE;
+
+const NUL: u8 = 0x00;
+const LF: u8 = b'\n';
+const VT: u8 = 0x0B;
+const FF: u8 = 0x0C;
+const CR: u8 = b'\r';
+
+/// Converts plain text into Telnet format effectors, where a CR
+/// which doesn't end a line must be followed by NUL.
+pub struct TelnetEncoder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    pending_cr: bool,
+}
+
+impl<W: Write> TelnetEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            pending_cr: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Writes a trailing CR held back waiting for the byte following it.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.pending_cr {
+            self.writer.write_all(&[CR, NUL])?;
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for TelnetEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        for &c in buf {
+            if std::mem::take(&mut self.pending_cr) {
+                self.buffer.push(CR);
+                if c != LF {
+                    self.buffer.push(NUL);
+                }
+            }
+
+            if c == CR {
+                self.pending_cr = true;
+            } else {
+                self.buffer.push(c);
+            }
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Converts Telnet format effectors into plain text, dropping the NUL
+/// following a bare CR and advancing a line on a vertical tab.
+pub struct TelnetDecoder<R: Read> {
+    reader: R,
+    decoded: Vec<u8>,
+    position: usize,
+    pending_cr: bool,
+}
+
+impl<R: Read> TelnetDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoded: Vec::new(),
+            position: 0,
+            pending_cr: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for TelnetDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.decoded.len() {
+            self.decoded.clear();
+            self.position = 0;
+
+            let mut input = [0u8; BUFFER_SIZE];
+            let read = self.reader.read(&mut input)?;
+            if read == 0 {
+                if std::mem::take(&mut self.pending_cr) {
+                    self.decoded.push(CR);
+                    continue;
+                }
+
+                return Ok(0);
+            }
+
+            for &c in &input[..read] {
+                if std::mem::take(&mut self.pending_cr) {
+                    self.decoded.push(CR);
+                    if c == NUL {
+                        continue;
+                    }
+                }
+
+                match c {
+                    CR => self.pending_cr = true,
+                    VT => self.decoded.push(LF),
+                    c => self.decoded.push(c),
+                }
+            }
+        }
+
+        let amount = buf.len().min(self.decoded.len() - self.position);
+        buf[..amount].copy_from_slice(&self.decoded[self.position..self.position + amount]);
+        self.position += amount;
+        Ok(amount)
+    }
+}
+
+/// Converts plain text into lines starting with an ASA carriage control
+/// character: ' ' advances one line, '0' two lines, '-' three lines,
+/// '1' starts a new page and '+' overprints the previous line.
+pub struct CarriageEncoder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    first_line: bool,
+    in_line: bool,
+    // Format effectors seen since the last line ended.
+    line_feeds: usize,
+    form_feed: bool,
+    carriage_return: bool,
+}
+
+impl<W: Write> CarriageEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            first_line: true,
+            in_line: false,
+            line_feeds: 0,
+            form_feed: false,
+            carriage_return: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Ends the last line.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if !self.first_line {
+            self.writer.write_all(&[LF])?;
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn start_line(&mut self) {
+        // The first line already is at the top of the page,
+        // any other line ends the one before it.
+        if !self.first_line {
+            self.buffer.push(LF);
+        }
+
+        let control = if self.form_feed {
+            b'1'
+        } else if self.carriage_return && self.line_feeds == 0 && !self.first_line {
+            b'+'
+        } else {
+            let mut advance = if self.first_line {
+                self.line_feeds + 1
+            } else {
+                self.line_feeds.max(1)
+            };
+
+            // Blank lines beyond what a single control can advance.
+            while advance > 3 {
+                self.buffer.extend_from_slice(&[b'-', LF]);
+                advance -= 3;
+            }
+
+            match advance {
+                1 => b' ',
+                2 => b'0',
+                _ => b'-',
+            }
+        };
+
+        self.buffer.push(control);
+        self.first_line = false;
+        self.in_line = true;
+        self.line_feeds = 0;
+        self.form_feed = false;
+        self.carriage_return = false;
+    }
+}
+
+impl<W: Write> Write for CarriageEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        for &c in buf {
+            match c {
+                LF => {
+                    self.in_line = false;
+                    self.line_feeds += 1;
+                }
+                FF => {
+                    self.in_line = false;
+                    self.form_feed = true;
+                }
+                CR => {
+                    self.in_line = false;
+                    self.carriage_return = true;
+                }
+                c => {
+                    if !self.in_line {
+                        self.start_line();
+                    }
+
+                    self.buffer.push(c);
+                }
+            }
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Converts lines starting with an ASA carriage control character into
+/// plain text, following the POSIX `asa` utility.
+pub struct CarriageDecoder<R: Read> {
+    reader: R,
+    decoded: Vec<u8>,
+    position: usize,
+    first_line: bool,
+    line_start: bool,
+    ended: bool,
+}
+
+impl<R: Read> CarriageDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoded: Vec::new(),
+            position: 0,
+            first_line: true,
+            line_start: true,
+            ended: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn control(&mut self, control: u8) {
+        let effectors: &[u8] = match (self.first_line, control) {
+            (true, b'0') => b"\n",
+            (true, b'-') => b"\n\n",
+            (true, b'1') => b"\x0c",
+            (true, _) => b"",
+            (false, b'0') => b"\n\n",
+            (false, b'-') => b"\n\n\n",
+            (false, b'1') => b"\n\x0c",
+            (false, b'+') => b"\r",
+            (false, _) => b"\n",
+        };
+
+        self.decoded.extend_from_slice(effectors);
+        self.first_line = false;
+    }
+}
+
+impl<R: Read> Read for CarriageDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.decoded.len() {
+            self.decoded.clear();
+            self.position = 0;
+            if self.ended {
+                return Ok(0);
+            }
+
+            let mut input = [0u8; BUFFER_SIZE];
+            let read = self.reader.read(&mut input)?;
+            if read == 0 {
+                // End the last line.
+                self.ended = true;
+                if !self.first_line {
+                    self.decoded.push(LF);
+                }
+
+                continue;
+            }
+
+            for &c in &input[..read] {
+                if std::mem::take(&mut self.line_start) {
+                    if c == LF {
+                        // An empty line without a control character.
+                        self.control(b' ');
+                        self.line_start = true;
+                    } else {
+                        self.control(c);
+                    }
+                } else if c == LF {
+                    self.line_start = true;
+                } else {
+                    self.decoded.push(c);
+                }
+            }
+        }
+
+        let amount = buf.len().min(self.decoded.len() - self.position);
+        buf[..amount].copy_from_slice(&self.decoded[self.position..self.position + amount]);
+        self.position += amount;
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::{CarriageDecoder, CarriageEncoder, TelnetDecoder, TelnetEncoder};
+
+    const PLAIN: &[u8] = b"TITLE\n\nfirst\nsecond\rUNDERLINE\n\x0cpage two\n\n\n\n\nend\n";
+    const ASA: &[u8] = b" TITLE\n0first\n second\n+UNDERLINE\n1page two\n-\n0end\n";
+
+    #[test]
+    fn test_carriage_encoder() {
+        let mut encoder = CarriageEncoder::new(Vec::new());
+        for chunk in PLAIN.chunks(3) {
+            encoder.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(encoder.finish().unwrap(), ASA);
+    }
+
+    #[test]
+    fn test_carriage_decoder() {
+        let mut decoder = CarriageDecoder::new(ASA);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, PLAIN);
+    }
+
+    #[test]
+    fn test_telnet_round_trip() {
+        let mut encoder = TelnetEncoder::new(Vec::new());
+        encoder.write_all(b"bold\rbold\r\n").unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded, b"bold\r\0bold\r\n");
+
+        let received = [&encoded[..], b"\x0bnext"].concat();
+        let mut decoder = TelnetDecoder::new(&received[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"bold\rbold\r\n\nnext");
+    }
+}
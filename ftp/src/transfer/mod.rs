@@ -0,0 +1,36 @@
+//! Adapters for data connections, converting between the local
+//! representation of a file and the one negotiated for the transfer.
+//!
+//! Encoders wrap the `Write` half of a connection and convert what is
+//! written to them before sending it, while decoders wrap the `Read`
+//! half and convert what is received before it is read from them.
+//!
+//! Encoders holding back data until they know what follows it
+//! write it out when finished.
+
+mod ascii;
+mod block;
+mod compressed;
+mod deflate;
+mod ebcdic;
+mod format;
+mod mode;
+mod page;
+mod record;
+mod representation;
+
+pub use ascii::{AsciiDecoder, AsciiEncoder, LineEnding};
+pub use block::{BlockDecoder, BlockEncoder};
+pub use compressed::{CompressedDecoder, CompressedEncoder};
+pub use deflate::{level_option, parse_level_option, DeflateDecoder, DeflateEncoder};
+pub use ebcdic::{CodePage, EbcdicDecoder, EbcdicEncoder};
+pub use format::{CarriageDecoder, CarriageEncoder, TelnetDecoder, TelnetEncoder};
+pub use mode::{ModeDecoder, ModeEncoder};
+pub use page::{Page, PageReader, PageType, PageWriter};
+pub use record::{RecordReader, RecordWriter};
+pub use representation::{
+    FormatDecoder, FormatEncoder, RepresentationDecoder, RepresentationEncoder, TextOptions,
+};
+
+// Amount of bytes read from a connection at once.
+const BUFFER_SIZE: usize = 1024 * 16;
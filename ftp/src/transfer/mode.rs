@@ -0,0 +1,162 @@
+use std::io::{Read, Write};
+
+use crate::command::{RepresentationTypeKind, TransferModeKind};
+
+use super::{
+    deflate::DEFAULT_LEVEL, BlockDecoder, BlockEncoder, CompressedDecoder, CompressedEncoder,
+    DeflateDecoder, DeflateEncoder,
+};
+
+/// Encodes data for the transfer mode of a `MODE` command. Stream mode
+/// sends data unchanged, and the end of the file by closing the connection.
+pub enum ModeEncoder<W: Write> {
+    Stream(W),
+    Block(BlockEncoder<W>),
+    Compressed(CompressedEncoder<W>),
+    Deflate(DeflateEncoder<W>),
+}
+
+impl<W: Write> ModeEncoder<W> {
+    /// Creates an encoder for data in the representation type `representation`,
+    /// which compressed mode needs to know its filler byte.
+    pub fn new(mode: TransferModeKind, representation: RepresentationTypeKind, writer: W) -> Self {
+        Self::with_level(mode, representation, writer, DEFAULT_LEVEL)
+    }
+
+    /// Creates an encoder like `new`, which compresses deflate mode data at
+    /// a level from 0 to 9, such as one set with `OPTS MODE Z LEVEL`.
+    pub fn with_level(
+        mode: TransferModeKind,
+        representation: RepresentationTypeKind,
+        writer: W,
+        level: u32,
+    ) -> Self {
+        match mode {
+            TransferModeKind::Stream => Self::Stream(writer),
+            TransferModeKind::Block => Self::Block(BlockEncoder::new(writer)),
+            TransferModeKind::Compressed => {
+                Self::Compressed(CompressedEncoder::new(writer, representation))
+            }
+            TransferModeKind::Deflate => Self::Deflate(DeflateEncoder::with_level(writer, level)),
+        }
+    }
+
+    /// Sends the end of the file and returns the data connection.
+    pub fn finish(self) -> std::io::Result<W> {
+        match self {
+            Self::Stream(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            Self::Block(encoder) => encoder.finish(),
+            Self::Compressed(encoder) => encoder.finish(),
+            Self::Deflate(encoder) => encoder.finish(),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Stream(writer) => writer,
+            Self::Block(encoder) => encoder,
+            Self::Compressed(encoder) => encoder,
+            Self::Deflate(encoder) => encoder,
+        }
+    }
+}
+
+impl<W: Write> Write for ModeEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+/// Decodes data received in the transfer mode of a `MODE` command.
+pub enum ModeDecoder<R: Read> {
+    Stream(R),
+    Block(BlockDecoder<R>),
+    Compressed(CompressedDecoder<R>),
+    Deflate(DeflateDecoder<R>),
+}
+
+impl<R: Read> ModeDecoder<R> {
+    pub fn new(mode: TransferModeKind, representation: RepresentationTypeKind, reader: R) -> Self {
+        match mode {
+            TransferModeKind::Stream => Self::Stream(reader),
+            TransferModeKind::Block => Self::Block(BlockDecoder::new(reader)),
+            TransferModeKind::Compressed => {
+                Self::Compressed(CompressedDecoder::new(reader, representation))
+            }
+            TransferModeKind::Deflate => Self::Deflate(DeflateDecoder::new(reader)),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        match self {
+            Self::Stream(reader) => reader,
+            Self::Block(decoder) => decoder.into_inner(),
+            Self::Compressed(decoder) => decoder.into_inner(),
+            Self::Deflate(decoder) => decoder.into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for ModeDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Stream(reader) => reader.read(buf),
+            Self::Block(decoder) => decoder.read(buf),
+            Self::Compressed(decoder) => decoder.read(buf),
+            Self::Deflate(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::command::{RepresentationTypeKind, TransferModeKind};
+
+    use super::{ModeDecoder, ModeEncoder};
+
+    #[test]
+    fn test_every_mode_round_trips() {
+        let data = b"text   with    runs\r\n".repeat(100);
+        let representation = RepresentationTypeKind::Ascii(None);
+        for mode in [
+            TransferModeKind::Stream,
+            TransferModeKind::Block,
+            TransferModeKind::Compressed,
+            TransferModeKind::Deflate,
+        ] {
+            let mut encoder = ModeEncoder::new(mode, representation, Vec::new());
+            encoder.write_all(&data).unwrap();
+            let encoded = encoder.finish().unwrap();
+
+            let mut decoder = ModeDecoder::new(mode, representation, &encoded[..]);
+            let mut output = Vec::new();
+            decoder.read_to_end(&mut output).unwrap();
+            assert_eq!(output, data, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_deflate_level() {
+        let data = b"text   with    runs\r\n".repeat(100);
+        let representation = RepresentationTypeKind::Ascii(None);
+        let encode = |level| {
+            let mode = TransferModeKind::Deflate;
+            let mut encoder = ModeEncoder::with_level(mode, representation, Vec::new(), level);
+            encoder.write_all(&data).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        // Level 0 stores the data uncompressed.
+        assert!(encode(0).len() > data.len());
+        assert!(encode(9).len() < data.len() / 10);
+    }
+}
@@ -0,0 +1,328 @@
+//! Page structure according to RFC 959 section 3.1.2.3, where every page
+//! is preceded by a header of logical bytes: the header length, the page
+//! index, the data length, the page type and any optional fields.
+//!
+//! Logical bytes are the byte size of a `TYPE L` transfer, of which only
+//! multiples of 8 bits are supported. Other transfers use 8 bit bytes.
+
+use std::io::{Read, Write};
+
+/// Header fields present in every page header.
+const REQUIRED_FIELDS: usize = 4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PageType {
+    /// Ends a paged transfer. Has no data.
+    Last,
+    Simple,
+    /// Describes the file as a whole.
+    Descriptor,
+    /// A page with an access control field, as used by TOPS-20.
+    AccessControlled {
+        access_control: u64,
+    },
+}
+
+impl PageType {
+    fn code(&self) -> u64 {
+        match self {
+            PageType::Last => 0,
+            PageType::Simple => 1,
+            PageType::Descriptor => 2,
+            PageType::AccessControlled { .. } => 3,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Page {
+    pub index: u64,
+    pub page_type: PageType,
+    pub data: Vec<u8>,
+}
+
+/// Sends pages over a data connection. The last page is sent once finished.
+pub struct PageWriter<W: Write> {
+    writer: W,
+    // Octets per logical byte.
+    byte_size: usize,
+    next_index: u64,
+}
+
+impl<W: Write> PageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            byte_size: 1,
+            next_index: 0,
+        }
+    }
+
+    /// Creates a writer for a `TYPE L` transfer of `bits` per logical byte.
+    pub fn with_byte_size(writer: W, bits: u8) -> std::io::Result<Self> {
+        Ok(Self {
+            writer,
+            byte_size: byte_size(bits)?,
+            next_index: 0,
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Sends a page, whose data must be a whole number of logical bytes.
+    pub fn write_page(&mut self, page: &Page) -> std::io::Result<()> {
+        if !page.data.len().is_multiple_of(self.byte_size) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "page data must be a whole number of logical bytes",
+            ));
+        }
+
+        let mut fields = vec![
+            0,
+            page.index,
+            (page.data.len() / self.byte_size) as u64,
+            page.page_type.code(),
+        ];
+        if let PageType::AccessControlled { access_control } = page.page_type {
+            fields.push(access_control);
+        }
+        fields[0] = fields.len() as u64;
+
+        let mut header = Vec::with_capacity(fields.len() * self.byte_size);
+        for field in fields {
+            let bytes = field.to_be_bytes();
+            let (overflow, value) = bytes.split_at(bytes.len().saturating_sub(self.byte_size));
+            if overflow.iter().any(|&c| c != 0) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "page header field doesn't fit a logical byte",
+                ));
+            }
+
+            // Bytes wider than a u64 are padded with leading zeros.
+            header.resize(header.len() + self.byte_size - value.len(), 0);
+            header.extend_from_slice(value);
+        }
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&page.data)?;
+        self.next_index = page.index + 1;
+        Ok(())
+    }
+
+    /// Sends the last page.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_page(&Page {
+            index: self.next_index,
+            page_type: PageType::Last,
+            data: Vec::new(),
+        })?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Receives pages from a data connection.
+pub struct PageReader<R: Read> {
+    reader: R,
+    byte_size: usize,
+    ended: bool,
+}
+
+impl<R: Read> PageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            byte_size: 1,
+            ended: false,
+        }
+    }
+
+    /// Creates a reader for a `TYPE L` transfer of `bits` per logical byte.
+    pub fn with_byte_size(reader: R, bits: u8) -> std::io::Result<Self> {
+        Ok(Self {
+            reader,
+            byte_size: byte_size(bits)?,
+            ended: false,
+        })
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next page, or `None` once the last page was received.
+    /// Optional header fields other than access control are skipped.
+    pub fn read_page(&mut self) -> std::io::Result<Option<Page>> {
+        if self.ended {
+            return Ok(None);
+        }
+
+        let header_length = self.read_field()?;
+        if header_length < REQUIRED_FIELDS as u64 {
+            return Err(invalid_data("page header is too short"));
+        }
+
+        let index = self.read_field()?;
+        let data_length = self.read_field()?;
+        let code = self.read_field()?;
+        let mut optional = Vec::new();
+        for _ in REQUIRED_FIELDS as u64..header_length {
+            optional.push(self.read_field()?);
+        }
+
+        let page_type = match (code, optional.first()) {
+            (0, _) => PageType::Last,
+            (1, _) => PageType::Simple,
+            (2, _) => PageType::Descriptor,
+            (3, Some(&access_control)) => PageType::AccessControlled { access_control },
+            (3, None) => return Err(invalid_data("access controlled page lacks access control")),
+            _ => return Err(invalid_data("unknown page type")),
+        };
+
+        let length = usize::try_from(data_length)
+            .ok()
+            .and_then(|length| length.checked_mul(self.byte_size))
+            .ok_or_else(|| invalid_data("page is too long"))?;
+        // The length comes from the peer, so memory is only
+        // allocated as the data arrives.
+        let mut data = Vec::new();
+        (&mut self.reader)
+            .take(length as u64)
+            .read_to_end(&mut data)?;
+        if data.len() != length {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        if page_type == PageType::Last {
+            self.ended = true;
+            return Ok(None);
+        }
+
+        Ok(Some(Page {
+            index,
+            page_type,
+            data,
+        }))
+    }
+
+    fn read_field(&mut self) -> std::io::Result<u64> {
+        let mut field = vec![0u8; self.byte_size];
+        self.reader.read_exact(&mut field)?;
+
+        let (overflow, value) = field.split_at(field.len().saturating_sub(8));
+        if overflow.iter().any(|&c| c != 0) {
+            return Err(invalid_data("page header field is too large"));
+        }
+
+        Ok(value.iter().fold(0, |field, &c| field << 8 | u64::from(c)))
+    }
+}
+
+impl<R: Read> Iterator for PageReader<R> {
+    type Item = std::io::Result<Page>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_page().transpose()
+    }
+}
+
+fn byte_size(bits: u8) -> std::io::Result<usize> {
+    if bits == 0 || !bits.is_multiple_of(8) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "logical byte size must be a multiple of 8 bits",
+        ));
+    }
+
+    Ok(usize::from(bits / 8))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, PageReader, PageType, PageWriter};
+
+    fn pages() -> Vec<Page> {
+        vec![
+            Page {
+                index: 0,
+                page_type: PageType::Descriptor,
+                data: b"dir".to_vec(),
+            },
+            Page {
+                index: 7,
+                page_type: PageType::AccessControlled { access_control: 5 },
+                data: b"data".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = PageWriter::new(Vec::new());
+        for page in pages() {
+            writer.write_page(&page).unwrap();
+        }
+        let encoded = writer.finish().unwrap();
+        assert_eq!(
+            encoded,
+            b"\x04\x00\x03\x02dir\x05\x07\x04\x03\x05data\x04\x08\x00\x00".to_vec()
+        );
+
+        let read: Vec<_> = PageReader::new(&encoded[..])
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, pages());
+    }
+
+    #[test]
+    fn test_wide_logical_bytes() {
+        let mut writer = PageWriter::with_byte_size(Vec::new(), 16).unwrap();
+        writer.write_page(&pages()[1]).unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(&encoded[..10], b"\x00\x05\x00\x07\x00\x02\x00\x03\x00\x05");
+
+        let mut reader = PageReader::with_byte_size(&encoded[..], 16).unwrap();
+        assert_eq!(reader.read_page().unwrap(), Some(pages()[1].clone()));
+        assert_eq!(reader.read_page().unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_page() {
+        let mut header = Vec::new();
+        for field in [4u64, 0, 1 << 40, 1] {
+            header.extend_from_slice(&field.to_be_bytes());
+        }
+        header.extend_from_slice(b"data");
+
+        let mut reader = PageReader::with_byte_size(&header[..], 64).unwrap();
+        assert_eq!(
+            reader.read_page().unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_field_overflow() {
+        let mut writer = PageWriter::new(Vec::new());
+        let page = Page {
+            index: 256,
+            page_type: PageType::Simple,
+            data: Vec::new(),
+        };
+
+        assert!(writer.write_page(&page).is_err());
+        assert!(PageWriter::with_byte_size(Vec::new(), 36).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Record structure in stream mode according to RFC 959 section 3.4.1,
+//! where 0xFF escapes the end of a record and the end of the file.
+
+use std::io::{Read, Write};
+
+use super::BUFFER_SIZE;
+
+const ESCAPE: u8 = 0xFF;
+const END_OF_RECORD: u8 = 0x01;
+const END_OF_FILE: u8 = 0x02;
+
+/// Sends records over a stream mode data connection. What is written to it
+/// is part of the current record, which `end_record` ends.
+pub struct RecordWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    records: u64,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            records: 0,
+        }
+    }
+
+    /// Amount of records ended so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Sends a whole record.
+    pub fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        self.write_all(record)?;
+        self.end_record()
+    }
+
+    pub fn end_record(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&[ESCAPE, END_OF_RECORD])?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Sends the end of the file.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.write_all(&[ESCAPE, END_OF_FILE])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for RecordWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        for &c in buf {
+            if c == ESCAPE {
+                self.buffer.push(ESCAPE);
+            }
+
+            self.buffer.push(c);
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Receives records from a stream mode data connection.
+pub struct RecordReader<R: Read> {
+    reader: R,
+    input: Vec<u8>,
+    position: usize,
+    end_of_file: bool,
+    records: u64,
+}
+
+impl<R: Read> RecordReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            input: Vec::new(),
+            position: 0,
+            end_of_file: false,
+            records: 0,
+        }
+    }
+
+    /// Amount of records read so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next record, or `None` once the file ended. A connection
+    /// closing before the end of the file is an `UnexpectedEof` error.
+    pub fn read_record(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let mut record = Vec::new();
+        while !self.end_of_file {
+            match self.next_byte()? {
+                ESCAPE => {
+                    let control = self.next_byte()?;
+                    if control == ESCAPE {
+                        record.push(ESCAPE);
+                        continue;
+                    }
+
+                    if control & !(END_OF_RECORD | END_OF_FILE) != 0 || control == 0 {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "invalid record control code",
+                        ));
+                    }
+
+                    self.end_of_file = control & END_OF_FILE != 0;
+                    if control & END_OF_RECORD != 0 {
+                        self.records += 1;
+                        return Ok(Some(record));
+                    }
+                }
+                c => record.push(c),
+            }
+        }
+
+        // A last record the end of the file implicitly ends.
+        if record.is_empty() {
+            Ok(None)
+        } else {
+            self.records += 1;
+            Ok(Some(record))
+        }
+    }
+
+    fn next_byte(&mut self) -> std::io::Result<u8> {
+        if self.position == self.input.len() {
+            self.input.resize(BUFFER_SIZE, 0);
+            let read = self.reader.read(&mut self.input)?;
+            self.input.truncate(read);
+            self.position = 0;
+            if read == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+
+        self.position += 1;
+        Ok(self.input[self.position - 1])
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordReader, RecordWriter};
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = RecordWriter::new(Vec::new());
+        writer.write_record(b"first").unwrap();
+        writer.write_record(b"").unwrap();
+        writer.write_record(b"\xff\x01").unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(
+            encoded,
+            b"first\xff\x01\xff\x01\xff\xff\x01\xff\x01\xff\x02"
+        );
+
+        let records: Vec<_> = RecordReader::new(&encoded[..])
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+        assert_eq!(records, [&b"first"[..], b"", b"\xff\x01"]);
+    }
+
+    #[test]
+    fn test_end_of_record_and_file() {
+        let mut reader = RecordReader::new(&b"a\xff\x01b\xff\x03"[..]);
+
+        assert_eq!(reader.read_record().unwrap(), Some(b"a".to_vec()));
+        assert_eq!(reader.read_record().unwrap(), Some(b"b".to_vec()));
+        assert_eq!(reader.read_record().unwrap(), None);
+        assert_eq!(reader.records(), 2);
+    }
+
+    #[test]
+    fn test_missing_end_of_file() {
+        let mut reader = RecordReader::new(&b"a\xff\x01b"[..]);
+
+        assert_eq!(reader.read_record().unwrap(), Some(b"a".to_vec()));
+        assert_eq!(
+            reader.read_record().unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+}
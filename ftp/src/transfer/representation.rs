@@ -0,0 +1,298 @@
+use std::io::{Read, Write};
+
+use crate::command::{FormatControl, RepresentationTypeKind};
+
+use super::{
+    AsciiDecoder, AsciiEncoder, CarriageDecoder, CarriageEncoder, CodePage, EbcdicDecoder,
+    EbcdicEncoder, LineEnding, TelnetDecoder, TelnetEncoder,
+};
+
+/// How local text is stored, for the representation types converting it.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TextOptions {
+    pub line_ending: LineEnding,
+    /// The code page of `TYPE E` transfers.
+    pub code_page: CodePage,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::NATIVE,
+            code_page: CodePage::CP037,
+        }
+    }
+}
+
+/// Encodes local files for the representation type of a `TYPE` command.
+/// Image and local byte types are sent unchanged.
+pub enum RepresentationEncoder<W: Write> {
+    Ascii(FormatEncoder<AsciiEncoder<W>>),
+    // Boxed as its code page tables are large.
+    Ebcdic(Box<FormatEncoder<EbcdicEncoder<W>>>),
+    Image(W),
+}
+
+impl<W: Write> RepresentationEncoder<W> {
+    pub fn new(kind: RepresentationTypeKind, writer: W, options: &TextOptions) -> Self {
+        match kind {
+            RepresentationTypeKind::Ascii(format) => {
+                Self::Ascii(FormatEncoder::new(format, AsciiEncoder::new(writer)))
+            }
+            RepresentationTypeKind::Ebcdic(format) => Self::Ebcdic(Box::new(FormatEncoder::new(
+                format,
+                EbcdicEncoder::new(writer, options.code_page.clone()),
+            ))),
+            RepresentationTypeKind::Image | RepresentationTypeKind::LocalByte(_) => {
+                Self::Image(writer)
+            }
+        }
+    }
+
+    /// The data connection, which encoded data not held back was written to.
+    pub fn get_mut(&mut self) -> &mut W {
+        match self {
+            Self::Ascii(encoder) => encoder.get_mut().get_mut(),
+            Self::Ebcdic(encoder) => encoder.get_mut().get_mut(),
+            Self::Image(writer) => writer,
+        }
+    }
+
+    /// Writes whatever the encoders hold back and returns the data connection.
+    pub fn finish(self) -> std::io::Result<W> {
+        match self {
+            Self::Ascii(encoder) => encoder.finish()?.finish(),
+            Self::Ebcdic(encoder) => encoder.finish()?.finish(),
+            Self::Image(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Ascii(encoder) => encoder,
+            Self::Ebcdic(encoder) => encoder,
+            Self::Image(writer) => writer,
+        }
+    }
+}
+
+impl<W: Write> Write for RepresentationEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+/// Encodes plain text for the format control of a text representation type.
+/// Non print, which is also the default, sends the text unchanged.
+pub enum FormatEncoder<W: Write> {
+    NonPrint(W),
+    Telnet(TelnetEncoder<W>),
+    Carriage(CarriageEncoder<W>),
+}
+
+impl<W: Write> FormatEncoder<W> {
+    pub fn new(format: Option<FormatControl>, writer: W) -> Self {
+        match format {
+            None | Some(FormatControl::NonPrint) => Self::NonPrint(writer),
+            Some(FormatControl::Telnet) => Self::Telnet(TelnetEncoder::new(writer)),
+            Some(FormatControl::Carriage) => Self::Carriage(CarriageEncoder::new(writer)),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        match self {
+            Self::NonPrint(writer) => writer,
+            Self::Telnet(encoder) => encoder.get_mut(),
+            Self::Carriage(encoder) => encoder.get_mut(),
+        }
+    }
+
+    pub fn finish(self) -> std::io::Result<W> {
+        match self {
+            Self::NonPrint(writer) => Ok(writer),
+            Self::Telnet(encoder) => encoder.finish(),
+            Self::Carriage(encoder) => encoder.finish(),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::NonPrint(writer) => writer,
+            Self::Telnet(encoder) => encoder,
+            Self::Carriage(encoder) => encoder,
+        }
+    }
+}
+
+impl<W: Write> Write for FormatEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+/// Decodes files received in the representation type of a `TYPE` command.
+/// Image and local byte types are received unchanged.
+pub enum RepresentationDecoder<R: Read> {
+    Ascii(FormatDecoder<AsciiDecoder<R>>),
+    // Boxed as its code page table is large.
+    Ebcdic(Box<FormatDecoder<EbcdicDecoder<R>>>),
+    Image(R),
+}
+
+impl<R: Read> RepresentationDecoder<R> {
+    pub fn new(kind: RepresentationTypeKind, reader: R, options: &TextOptions) -> Self {
+        match kind {
+            RepresentationTypeKind::Ascii(format) => Self::Ascii(FormatDecoder::new(
+                format,
+                AsciiDecoder::with_line_ending(reader, options.line_ending),
+            )),
+            RepresentationTypeKind::Ebcdic(format) => Self::Ebcdic(Box::new(FormatDecoder::new(
+                format,
+                EbcdicDecoder::with_line_ending(
+                    reader,
+                    options.code_page.clone(),
+                    options.line_ending,
+                ),
+            ))),
+            RepresentationTypeKind::Image | RepresentationTypeKind::LocalByte(_) => {
+                Self::Image(reader)
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        match self {
+            Self::Ascii(decoder) => decoder.get_mut().get_mut(),
+            Self::Ebcdic(decoder) => decoder.get_mut().get_mut(),
+            Self::Image(reader) => reader,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        match self {
+            Self::Ascii(decoder) => decoder.into_inner().into_inner(),
+            Self::Ebcdic(decoder) => decoder.into_inner().into_inner(),
+            Self::Image(reader) => reader,
+        }
+    }
+}
+
+impl<R: Read> Read for RepresentationDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Ascii(decoder) => decoder.read(buf),
+            Self::Ebcdic(decoder) => decoder.read(buf),
+            Self::Image(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Decodes the format control of a text representation type into plain text.
+pub enum FormatDecoder<R: Read> {
+    NonPrint(R),
+    Telnet(TelnetDecoder<R>),
+    Carriage(CarriageDecoder<R>),
+}
+
+impl<R: Read> FormatDecoder<R> {
+    pub fn new(format: Option<FormatControl>, reader: R) -> Self {
+        match format {
+            None | Some(FormatControl::NonPrint) => Self::NonPrint(reader),
+            Some(FormatControl::Telnet) => Self::Telnet(TelnetDecoder::new(reader)),
+            Some(FormatControl::Carriage) => Self::Carriage(CarriageDecoder::new(reader)),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        match self {
+            Self::NonPrint(reader) => reader,
+            Self::Telnet(decoder) => decoder.get_mut(),
+            Self::Carriage(decoder) => decoder.get_mut(),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        match self {
+            Self::NonPrint(reader) => reader,
+            Self::Telnet(decoder) => decoder.into_inner(),
+            Self::Carriage(decoder) => decoder.into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for FormatDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::NonPrint(reader) => reader.read(buf),
+            Self::Telnet(decoder) => decoder.read(buf),
+            Self::Carriage(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::command::{FormatControl, RepresentationTypeKind};
+    use crate::transfer::{LineEnding, TextOptions};
+
+    use super::{RepresentationDecoder, RepresentationEncoder};
+
+    const PLAIN: &[u8] = b"TITLE\n\x0cpage two\n";
+
+    fn options() -> TextOptions {
+        TextOptions {
+            line_ending: LineEnding::Lf,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ascii_carriage_control() {
+        let kind = RepresentationTypeKind::Ascii(Some(FormatControl::Carriage));
+        let mut encoder = RepresentationEncoder::new(kind, Vec::new(), &options());
+        encoder.write_all(PLAIN).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded, b" TITLE\r\n1page two\r\n");
+
+        let mut decoder = RepresentationDecoder::new(kind, &encoded[..], &options());
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, PLAIN);
+    }
+
+    #[test]
+    fn test_ebcdic_carriage_control() {
+        let kind = RepresentationTypeKind::Ebcdic(Some(FormatControl::Carriage));
+        let mut encoder = RepresentationEncoder::new(kind, Vec::new(), &options());
+        encoder.write_all(b"A\nB\n").unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded, [0x40, 0xC1, 0x15, 0x40, 0xC2, 0x15]);
+
+        let mut decoder = RepresentationDecoder::new(kind, &encoded[..], &options());
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"A\nB\n");
+    }
+
+    #[test]
+    fn test_image_is_unchanged() {
+        let mut encoder =
+            RepresentationEncoder::new(RepresentationTypeKind::Image, Vec::new(), &options());
+        encoder.write_all(b"\r\n\x0c").unwrap();
+
+        assert_eq!(encoder.finish().unwrap(), b"\r\n\x0c");
+    }
+}
@@ -10,7 +10,7 @@ use libftp::serializer::Serializer;
 use libftp::{
     parser::{parse_command, parse_reply},
     serializer::CommandSerializer,
-    serializer::ReplySerializer,
+    serializer::{MultiLineMode, ReplySerializer},
 };
 
 const BUFFER_SIZE: usize = 1024 * 16;
@@ -35,7 +35,8 @@ pub fn proxy_connection(downstream: TcpStream, target: &SocketAddr) -> std::io::
             upstream_reader,
             parse_reply,
             |reply| reply,
-            ReplySerializer::new(downstream_writer),
+            // Forward replies exactly as the server sent them.
+            ReplySerializer::with_mode(downstream_writer, MultiLineMode::Lossless),
         )
     });
 
@@ -63,7 +64,7 @@ where
     let mut buffer_index = 0;
     loop {
         break match parse_stream(&mut buffer, &mut buffer_index, &mut reader, &parser)
-            .and_then(|parsed| Ok(hook(parsed)))
+            .map(&hook)
             .and_then(|parsed| serializer.serialize(&parsed))
         {
             Ok(()) => continue,